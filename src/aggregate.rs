@@ -0,0 +1,236 @@
+//! `aggregate` subcommand, which resamples the listings written by `listings` into fixed time
+//! buckets for charting
+use chrono::{DateTime, TimeZone, Utc};
+use clap::{App, Arg, ArgMatches, SubCommand};
+use failure;
+use itertools::Itertools;
+use output::{self, StoredListing};
+use spidy_scrapey::api;
+
+/// Length of a bucket
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Interval {
+    Hour,
+    Day,
+    /// Starting on Monday
+    Week,
+}
+
+const HOUR: i64 = 60 * 60;
+const DAY: i64 = 24 * HOUR;
+const WEEK: i64 = 7 * DAY;
+/// The Unix epoch was on a Thursday, so weeks start 4 days later
+const FIRST_MONDAY: i64 = 4 * DAY;
+
+impl Interval {
+    pub fn from_arg(value: &str) -> Self {
+        match value {
+            "hour" => Interval::Hour,
+            "week" => Interval::Week,
+            _ => Interval::Day,
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            Interval::Hour => "hour",
+            Interval::Day => "day",
+            Interval::Week => "week",
+        }
+    }
+
+    /// Start of the bucket that `time` falls in
+    pub fn start(self, time: DateTime<Utc>) -> DateTime<Utc> {
+        let (length, offset) = match self {
+            Interval::Hour => (HOUR, 0),
+            Interval::Day => (DAY, 0),
+            Interval::Week => (WEEK, FIRST_MONDAY),
+        };
+        let seconds = time.timestamp();
+        Utc.timestamp_opt(seconds - (seconds - offset).rem_euclid(length), 0)
+            .single()
+            .expect("the start of a bucket to be valid")
+    }
+}
+
+/// Listings of one type within a bucket
+#[derive(Serialize, Debug, PartialEq)]
+pub struct Bucket {
+    /// Start of the bucket
+    #[serde(with = "::spidy_scrapey::custom_serde::timestamp")]
+    pub timestamp: DateTime<Utc>,
+    #[serde(rename = "type")]
+    pub listing_type: api::ListingType,
+    pub open: u64,
+    pub high: u64,
+    pub low: u64,
+    pub close: u64,
+    pub min_quantity: u64,
+    pub max_quantity: u64,
+    pub avg_quantity: f64,
+    pub min_listings: u64,
+    pub max_listings: u64,
+    pub avg_listings: f64,
+    /// Number of listings in the bucket
+    pub samples: usize,
+}
+
+impl Bucket {
+    /// `listings` must be non-empty, of the same type and sorted oldest first
+    fn new(timestamp: DateTime<Utc>, listings: &[&StoredListing]) -> Self {
+        let count = listings.len() as f64;
+        let prices = || listings.iter().map(|listing| listing.unit_price);
+        let quantities = || listings.iter().map(|listing| listing.quantity);
+        let counts = || listings.iter().map(|listing| listing.listings);
+
+        Self {
+            timestamp,
+            listing_type: listings[0].listing_type,
+            open: listings[0].unit_price,
+            high: prices().max().expect("to be non-empty"),
+            low: prices().min().expect("to be non-empty"),
+            close: listings[listings.len() - 1].unit_price,
+            min_quantity: quantities().min().expect("to be non-empty"),
+            max_quantity: quantities().max().expect("to be non-empty"),
+            avg_quantity: quantities().sum::<u64>() as f64 / count,
+            min_listings: counts().min().expect("to be non-empty"),
+            max_listings: counts().max().expect("to be non-empty"),
+            avg_listings: counts().sum::<u64>() as f64 / count,
+            samples: listings.len(),
+        }
+    }
+}
+
+/// Buckets of the listings of one type, oldest first
+fn series(
+    listings: &[StoredListing],
+    listing_type: api::ListingType,
+    interval: Interval,
+) -> Vec<Bucket> {
+    let mut listings: Vec<&StoredListing> = listings
+        .iter()
+        .filter(|listing| listing.listing_type == listing_type)
+        .collect();
+    listings.sort_by_key(|listing| listing.timestamp);
+
+    let mut buckets = vec![];
+    let mut rest = &listings[..];
+    while !rest.is_empty() {
+        let start = interval.start(rest[0].timestamp);
+        let length = rest
+            .iter()
+            .take_while(|listing| interval.start(listing.timestamp) == start)
+            .count();
+        buckets.push(Bucket::new(start, &rest[..length]));
+        rest = &rest[length..];
+    }
+    buckets
+}
+
+/// Resample buy and sell listings into buckets, oldest first with buy before sell
+pub fn aggregate(listings: &[StoredListing], interval: Interval) -> Vec<Bucket> {
+    series(listings, api::ListingType::Buy, interval)
+        .into_iter()
+        .merge_by(
+            series(listings, api::ListingType::Sell, interval),
+            |left, right| left.timestamp <= right.timestamp,
+        ).collect()
+}
+
+pub fn subcommand<'a, 'b>() -> App<'a, 'b>
+where
+    'a: 'b,
+{
+    SubCommand::with_name("aggregate")
+        .about(
+            "Resample listing CSV files written by `listings` into fixed time buckets, with the \
+             open, high, low and close unit price, the range of quantities and listings and \
+             the number of listings in each. Each file is written to the output directory as FILE.INTERVAL.csv.",
+        ).arg(
+            Arg::with_name("file")
+                .help("Listing CSV file to aggregate")
+                .value_name("FILE")
+                .required(true)
+                .multiple(true),
+        ).arg(
+            Arg::with_name("interval")
+                .help("Length of each bucket")
+                .default_value("day")
+                .possible_values(&["hour", "day", "week"])
+                .long("--interval")
+                .takes_value(true),
+        )
+}
+
+pub fn run(args: &ArgMatches) -> Result<(), failure::Error> {
+    let interval = Interval::from_arg(args.value_of("interval").expect("Value to be present"));
+    output::transform_files(args, interval.name(), |listings| {
+        aggregate(listings, interval)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use output::stored_listing as listing;
+
+    fn utc(time: &str) -> DateTime<Utc> {
+        time.parse().unwrap()
+    }
+
+    #[test]
+    fn finds_the_start_of_buckets() {
+        let time = utc("2018-10-04T13:45:10Z");
+
+        assert_eq!(Interval::Hour.start(time), utc("2018-10-04T13:00:00Z"));
+        assert_eq!(Interval::Day.start(time), utc("2018-10-04T00:00:00Z"));
+        // Monday
+        assert_eq!(Interval::Week.start(time), utc("2018-10-01T00:00:00Z"));
+        assert_eq!(
+            Interval::Week.start(utc("2018-10-01T00:00:00Z")),
+            utc("2018-10-01T00:00:00Z")
+        );
+    }
+
+    #[test]
+    fn aggregates_listings_into_buckets() {
+        use spidy_scrapey::api::ListingType::{Buy, Sell};
+
+        let listings = vec![
+            listing("2018-10-01T01:00:00Z", Buy, 100, 10),
+            listing("2018-10-01T02:00:00Z", Sell, 200, 5),
+            listing("2018-10-01T03:00:00Z", Buy, 120, 20),
+            listing("2018-10-01T04:00:00Z", Buy, 90, 30),
+            listing("2018-10-01T05:00:00Z", Buy, 110, 60),
+            listing("2018-10-02T01:00:00Z", Buy, 130, 10),
+        ];
+
+        let buckets = aggregate(&listings, Interval::Day);
+
+        assert_eq!(buckets.len(), 3);
+        assert_eq!(
+            buckets[0],
+            Bucket {
+                timestamp: utc("2018-10-01T00:00:00Z"),
+                listing_type: Buy,
+                open: 100,
+                high: 120,
+                low: 90,
+                close: 110,
+                min_quantity: 10,
+                max_quantity: 60,
+                avg_quantity: 30.0,
+                min_listings: 1,
+                max_listings: 1,
+                avg_listings: 1.0,
+                samples: 4,
+            }
+        );
+        assert_eq!(
+            (buckets[1].listing_type, buckets[1].open, buckets[1].close),
+            (Sell, 200, 200)
+        );
+        assert_eq!(buckets[2].timestamp, utc("2018-10-02T00:00:00Z"));
+        assert_eq!((buckets[1].samples, buckets[2].samples), (1, 1));
+    }
+}
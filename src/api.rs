@@ -1,14 +1,29 @@
+#[cfg(feature = "async")]
+use async_api::AsyncApi;
 use backoff::backoff::Backoff;
 use backoff::ExponentialBackoff;
+use chrono::{DateTime, Utc};
+use csv;
+use custom_serde;
 use data;
-use reqwest::{Client, Error};
+use failure::Fail;
+use pacing::{Pacer, Pacing, RateLimiter};
+#[cfg(feature = "async")]
+use reqwest::async;
+use reqwest::header::{HeaderMap, HeaderValue, RETRY_AFTER, USER_AGENT};
+use reqwest::{Client, StatusCode};
 use serde::de::DeserializeOwned;
+use serde_json;
+use std::any::TypeId;
 use std::collections::VecDeque;
+use std::error::Error as StdError;
+use std::fmt;
 use std::iter::FromIterator;
 use std::marker;
 use std::thread::sleep;
 use std::time::Duration;
 
+/// A page of results from a paginated API
 pub trait PaginatedResult<T> {
     fn page(&self) -> u64;
     fn last_page(&self) -> u64;
@@ -18,68 +33,456 @@ pub trait PaginatedResult<T> {
     }
 }
 
+/// Response of an API method, which can be decoded from either `ApiFormat`
+pub trait ApiResponse: DeserializeOwned {
+    /// A row of the CSV variant of the response
+    type Row: DeserializeOwned;
+
+    /// Build the response from the rows of the CSV variant, requested for `page` of a paginated
+    /// API
+    fn from_rows(rows: Vec<Self::Row>, page: Option<u64>) -> Result<Self, DecodeError>;
+}
+
+/// Reasons a response body could not be decoded
+#[derive(Debug)]
+pub enum DecodeError {
+    Json(serde_json::Error),
+    Csv(csv::Error),
+    /// The CSV response had no rows where one was expected
+    Empty,
+}
+
+impl fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            DecodeError::Json(e) => write!(f, "{}", e),
+            DecodeError::Csv(e) => write!(f, "{}", e),
+            DecodeError::Empty => write!(f, "expected at least one CSV row"),
+        }
+    }
+}
+
+impl StdError for DecodeError {
+    fn source(&self) -> Option<&(dyn StdError + 'static)> {
+        match self {
+            DecodeError::Json(e) => Some(e),
+            DecodeError::Csv(e) => Some(e),
+            DecodeError::Empty => None,
+        }
+    }
+}
+
+/// Errors from calling the GW2Spidy API
+///
+/// `page` is the page number of a paginated request, or `None` for non-paginated requests.
+#[derive(Debug)]
+pub enum Error {
+    /// The HTTP client could not be built
+    Client(reqwest::Error),
+    /// The request failed to complete, e.g. connection errors or timeouts
+    Request {
+        url: String,
+        page: Option<u64>,
+        cause: reqwest::Error,
+    },
+    /// The API responded with a non-successful HTTP status
+    Status {
+        url: String,
+        page: Option<u64>,
+        status: StatusCode,
+        retry_after: Option<Duration>,
+    },
+    /// The response did not have the expected shape
+    Decode {
+        url: String,
+        page: Option<u64>,
+        cause: DecodeError,
+    },
+    /// The response contained a `Rarity` we do not know about
+    UnknownRarity {
+        url: String,
+        page: Option<u64>,
+        cause: DecodeError,
+    },
+    /// The response contained a timestamp we could not parse
+    InvalidTimestamp {
+        url: String,
+        page: Option<u64>,
+        cause: DecodeError,
+    },
+}
+
+impl Error {
+    /// `invalid` is the value that the deserializer recorded as the cause, if any
+    pub(crate) fn decode(
+        url: &str,
+        page: Option<u64>,
+        cause: DecodeError,
+        invalid: Option<custom_serde::Invalid>,
+    ) -> Self {
+        let url = url.to_string();
+        match invalid {
+            Some(custom_serde::Invalid::Variant(type_id))
+                if type_id == TypeId::of::<data::Rarity>() =>
+            {
+                Error::UnknownRarity { url, page, cause }
+            }
+            Some(custom_serde::Invalid::Timestamp) => Error::InvalidTimestamp { url, page, cause },
+            _ => Error::Decode { url, page, cause },
+        }
+    }
+
+    /// URL of the request that failed
+    pub fn url(&self) -> Option<&str> {
+        match self {
+            Error::Client(_) => None,
+            Error::Request { url, .. }
+            | Error::Status { url, .. }
+            | Error::Decode { url, .. }
+            | Error::UnknownRarity { url, .. }
+            | Error::InvalidTimestamp { url, .. } => Some(url),
+        }
+    }
+
+    /// Page number of the paginated request that failed
+    pub fn page(&self) -> Option<u64> {
+        match self {
+            Error::Client(_) => None,
+            Error::Request { page, .. }
+            | Error::Status { page, .. }
+            | Error::Decode { page, .. }
+            | Error::UnknownRarity { page, .. }
+            | Error::InvalidTimestamp { page, .. } => *page,
+        }
+    }
+
+    /// HTTP status returned by the API, if any
+    pub fn status(&self) -> Option<StatusCode> {
+        match self {
+            Error::Status { status, .. } => Some(*status),
+            _ => None,
+        }
+    }
+
+    /// Whether the request might succeed if retried: connection errors, timeouts, HTTP 429 and
+    /// HTTP 5xx
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            Error::Request { .. } => true,
+            Error::Status { status, .. } => {
+                *status == StatusCode::TOO_MANY_REQUESTS || status.is_server_error()
+            }
+            _ => false,
+        }
+    }
+
+    /// Delay requested by the API via the `Retry-After` header
+    pub fn retry_after(&self) -> Option<Duration> {
+        match self {
+            Error::Status { retry_after, .. } => *retry_after,
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Client(cause) => return write!(f, "Unable to create HTTP client: {}", cause),
+            Error::Request { cause, .. } => write!(f, "Request failed: {}", cause)?,
+            Error::Status { status, .. } => write!(f, "API responded with HTTP {}", status)?,
+            Error::Decode { cause, .. } => write!(f, "Unable to decode response: {}", cause)?,
+            Error::UnknownRarity { cause, .. } => write!(f, "Unknown rarity: {}", cause)?,
+            Error::InvalidTimestamp { cause, .. } => write!(f, "Invalid timestamp: {}", cause)?,
+        };
+
+        if let Some(page) = self.page() {
+            write!(f, " (page {})", page)?;
+        }
+        write!(f, " from {}", self.url().unwrap_or("unknown"))
+    }
+}
+
+impl Fail for Error {
+    fn cause(&self) -> Option<&dyn Fail> {
+        match self {
+            Error::Client(cause) | Error::Request { cause, .. } => Some(cause),
+            Error::Decode { cause, .. }
+            | Error::UnknownRarity { cause, .. }
+            | Error::InvalidTimestamp { cause, .. } => Some(cause),
+            Error::Status { .. } => None,
+        }
+    }
+}
+
+/// Parse a `Retry-After` header, which is either a number of seconds or an HTTP date
+pub(crate) fn retry_after(headers: &HeaderMap) -> Option<Duration> {
+    let value = headers.get(RETRY_AFTER)?.to_str().ok()?.trim();
+    match value.parse::<u64>() {
+        Ok(seconds) => Some(Duration::from_secs(seconds)),
+        Err(_) => {
+            let date = DateTime::parse_from_rfc2822(value).ok()?;
+            date.with_timezone(&Utc)
+                .signed_duration_since(Utc::now())
+                .to_std()
+                .ok()
+        }
+    }
+}
+
+/// Decode a response body in the given format
+pub(crate) fn decode<R>(
+    format: ApiFormat,
+    body: &str,
+    url: &str,
+    page: Option<u64>,
+) -> Result<R, Error>
+where
+    R: ApiResponse,
+{
+    // Discard anything recorded by an earlier deserialization that recovered from its error
+    custom_serde::take_invalid();
+    let result = match format {
+        ApiFormat::Json => serde_json::from_str(body).map_err(DecodeError::Json),
+        ApiFormat::Csv => csv::Reader::from_reader(body.as_bytes())
+            .deserialize()
+            .collect::<Result<Vec<R::Row>, _>>()
+            .map_err(DecodeError::Csv)
+            .and_then(|rows| R::from_rows(rows, page)),
+    };
+    result.map_err(|cause| Error::decode(url, page, cause, custom_serde::take_invalid()))
+}
+
+/// Make a GET request and decode the response
+fn get<R>(client: &Client, format: ApiFormat, url: &str, page: Option<u64>) -> Result<R, Error>
+where
+    R: ApiResponse,
+{
+    let request_error = |cause| Error::Request {
+        url: url.to_string(),
+        page,
+        cause,
+    };
+
+    let mut response = client.get(url).send().map_err(&request_error)?;
+    let status = response.status();
+    if !status.is_success() {
+        return Err(Error::Status {
+            url: url.to_string(),
+            page,
+            status,
+            retry_after: retry_after(response.headers()),
+        });
+    }
+
+    let body = response.text().map_err(&request_error)?;
+    decode(format, &body, url, page)
+}
+
+/// Policy for retrying requests that failed with a retryable error
+#[derive(Clone, Copy, Debug)]
+pub struct Retry {
+    /// Maximum number of attempts for each request, including the first one
+    pub max_attempts: u32,
+    /// Maximum time to spend retrying a request
+    pub max_elapsed_time: Duration,
+    /// Maximum delay between attempts
+    pub max_interval: Duration,
+}
+
+impl Retry {
+    pub(crate) fn new_backoff(&self) -> ExponentialBackoff {
+        ExponentialBackoff {
+            max_interval: self.max_interval,
+            max_elapsed_time: Some(self.max_elapsed_time),
+            ..Default::default()
+        }
+    }
+}
+
+impl Default for Retry {
+    fn default() -> Self {
+        Self {
+            max_attempts: 5,
+            max_elapsed_time: Duration::from_secs(300),
+            max_interval: Duration::from_secs(60),
+        }
+    }
+}
+
+/// Makes requests to the API, retrying them according to the `Retry` policy
+#[derive(Clone)]
+struct Fetcher {
+    client: Client,
+    format: ApiFormat,
+    retry: Retry,
+    rate_limiter: Option<RateLimiter>,
+}
+
+impl Fetcher {
+    fn get<R>(&self, url: &str, page: Option<u64>) -> Result<R, Error>
+    where
+        R: ApiResponse,
+    {
+        let mut backoff = self.retry.new_backoff();
+        let mut attempt = 1;
+
+        loop {
+            if let Some(ref rate_limiter) = self.rate_limiter {
+                rate_limiter.wait();
+            }
+
+            let error = match get(&self.client, self.format, url, page) {
+                Ok(result) => return Ok(result),
+                Err(e) => e,
+            };
+
+            if !error.is_retryable() || attempt >= self.retry.max_attempts {
+                return Err(error);
+            }
+
+            let duration = match backoff.next_backoff() {
+                Some(duration) => duration.max(error.retry_after().unwrap_or_default()),
+                None => return Err(error),
+            };
+            warn!(
+                "{}; retrying in {}.{} seconds (attempt {} of {})",
+                error,
+                duration.as_secs(),
+                duration.subsec_millis(),
+                attempt + 1,
+                self.retry.max_attempts
+            );
+            sleep(duration);
+
+            attempt += 1;
+        }
+    }
+}
+
+pub const DEFAULT_BASE_URL: &str = "https://www.gw2spidy.com/api";
+pub const DEFAULT_VERSION: &str = "v0.9";
+
+/// Blocking client for the GW2Spidy API
 pub struct Api {
     version: String,
     format: ApiFormat,
     base_url: String,
-    max_interval: u64,
+    pacing: Pacing,
+    fetcher: Fetcher,
+}
+
+/// Builder for an `Api` client
+pub struct ApiBuilder {
+    version: String,
+    format: ApiFormat,
+    base_url: String,
+    pacing: Pacing,
+    timeout: Option<Duration>,
+    user_agent: Option<String>,
+    retry: Retry,
+    rate_limit: Option<(f64, u32)>,
 }
 
+/// Format of the API responses
+///
+/// CSV responses carry no pagination metadata, so each one is treated as the last page.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
 pub enum ApiFormat {
-    #[allow(dead_code)]
     Csv,
     Json,
 }
 
+/// Predicate given to `PaginatedIterator::until`
+type Until<T> = Box<dyn FnMut(&T) -> bool + Send>;
+
+/// Fetches the pages of a paginated API as it is iterated
 pub struct PaginatedIterator<R, T> {
     base_url: String,
-    client: Client,
+    fetcher: Fetcher,
     page_number: u64,
     total_pages: u64,
     page: VecDeque<T>,
-    backoff: ExponentialBackoff,
+    pacer: Box<dyn Pacer>,
     size_hint: Option<usize>,
+    on_page: Option<Box<dyn FnMut(u64) + Send>>,
+    until: Option<Until<T>>,
+    /// Set once `until` has matched, after which no more pages are fetched
+    stopped: bool,
     _marker: marker::PhantomData<R>,
 }
 
+impl<R, T> PaginatedIterator<R, T> {
+    /// Base URL of the paginated API, without the page number
+    pub fn url(&self) -> &str {
+        &self.base_url
+    }
+
+    /// Start fetching from `page` instead of the first page
+    pub fn starting_at(mut self, page: u64) -> Self {
+        self.page_number = page;
+        self.total_pages = page;
+        self
+    }
+
+    /// Call `f` with the page number every time a new page has been fetched
+    ///
+    /// By the time `f` is called, every result from the previous pages has been yielded.
+    pub fn on_page<F>(mut self, f: F) -> Self
+    where
+        F: FnMut(u64) + Send + 'static,
+    {
+        self.on_page = Some(Box::new(f));
+        self
+    }
+
+    /// Stop at the first result for which `predicate` returns true, without yielding it or
+    /// fetching any more pages
+    ///
+    /// Results that are sorted, such as listings from newest to oldest, can then be fetched up
+    /// to a point without paying for the rest of the pages.
+    pub fn until<P>(mut self, predicate: P) -> Self
+    where
+        P: FnMut(&T) -> bool + Send + 'static,
+    {
+        self.until = Some(Box::new(predicate));
+        self
+    }
+}
+
 impl<R, T> Iterator for PaginatedIterator<R, T>
 where
-    R: DeserializeOwned + PaginatedResult<T>,
+    R: ApiResponse + PaginatedResult<T>,
 {
     type Item = Result<T, Error>;
 
     fn next(&mut self) -> Option<<Self as Iterator>::Item> {
-        if self.page.is_empty() {
+        if self.stopped {
+            return None;
+        }
+
+        // Keep going past empty pages
+        while self.page.is_empty() {
             if self.page_number > self.total_pages {
                 // We are done
                 return None;
             } else {
                 // Request for a new page
-                let duration = self
-                    .backoff
-                    .next_backoff()
-                    .unwrap_or_else(|| Duration::new(0, 0));
+                let duration = self.pacer.delay();
                 debug!(
                     "Sleeping {}.{} seconds before the next request",
                     duration.as_secs(),
                     duration.subsec_millis()
                 );
+                sleep(duration);
 
                 let url = [&self.base_url, format!("{}", self.page_number).as_str()].join("/");
                 debug!("Making paginated requests for API {}", url);
-                let result = self.client.get(&url).send();
-
-                if let Err(e) = result {
-                    return Some(Err(e));
-                }
-
-                let result: Result<R, Error> = result.expect("OK to unwrap").json();
-
-                if let Err(e) = result {
-                    return Some(Err(e));
-                }
+                let result: R = match self.fetcher.get(&url, Some(self.page_number)) {
+                    Ok(result) => result,
+                    Err(e) => return Some(Err(e)),
+                };
 
-                let result = result.expect("OK to unwrap");
                 debug!("\t Page {} of {}", result.page(), result.last_page());
 
                 self.total_pages = result.last_page();
@@ -89,13 +492,33 @@ where
                     self.size_hint = result.count();
                 }
 
+                if let Some(ref mut on_page) = self.on_page {
+                    on_page(result.page());
+                }
+
                 self.page = VecDeque::from_iter(result.results().into_iter());
             }
         }
-        Some(Ok(self.page.pop_front().expect("Not to be empty")))
+
+        let result = self.page.pop_front().expect("Not to be empty");
+        if let Some(ref mut until) = self.until {
+            if until(&result) {
+                debug!(
+                    "Stopping paginated requests for API {} before page {}",
+                    self.base_url, self.page_number
+                );
+                self.stopped = true;
+                self.page.clear();
+                return None;
+            }
+        }
+        Some(Ok(result))
     }
 
     fn size_hint(&self) -> (usize, Option<usize>) {
+        if self.stopped {
+            return (0, Some(0));
+        }
         (0, self.size_hint)
     }
 }
@@ -109,23 +532,161 @@ impl ToString for ApiFormat {
     }
 }
 
-impl Api {
-    pub fn new(format: ApiFormat, max_interval: u64) -> Self {
-        debug!("Creating API Client for v0.9");
-        Self {
-            version: "v0.9".to_string(),
-            format: format,
-            base_url: "https://www.gw2spidy.com/api".to_string(),
-            max_interval,
+impl ApiBuilder {
+    /// Base URL of the API, without the version. Defaults to `DEFAULT_BASE_URL`.
+    pub fn base_url<S: Into<String>>(mut self, base_url: S) -> Self {
+        self.base_url = base_url.into().trim_end_matches('/').to_string();
+        self
+    }
+
+    /// API version to request. Defaults to `DEFAULT_VERSION`.
+    pub fn version<S: Into<String>>(mut self, version: S) -> Self {
+        self.version = version.into();
+        self
+    }
+
+    pub fn format(mut self, format: ApiFormat) -> Self {
+        self.format = format;
+        self
+    }
+
+    /// Max duration, in seconds, for the exponential backoff delay between API calls
+    pub fn max_backoff(mut self, max_interval: u64) -> Self {
+        self.pacing = Pacing::Exponential {
+            max_interval: Duration::from_secs(max_interval),
+        };
+        self
+    }
+
+    /// Strategy for pacing requests to paginated APIs
+    pub fn pacing(mut self, pacing: Pacing) -> Self {
+        self.pacing = pacing;
+        self
+    }
+
+    /// Timeout for each HTTP request
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    pub fn user_agent<S: Into<String>>(mut self, user_agent: S) -> Self {
+        self.user_agent = Some(user_agent.into());
+        self
+    }
+
+    /// Policy for retrying failed requests
+    pub fn retry(mut self, retry: Retry) -> Self {
+        self.retry = retry;
+        self
+    }
+
+    /// Limit all requests made by the `Api`, including retries and concurrent requests, to
+    /// `rate` requests per second with bursts of up to `burst` requests
+    pub fn rate_limit(mut self, rate: f64, burst: u32) -> Self {
+        self.rate_limit = Some((rate, burst));
+        self
+    }
+
+    fn default_headers(&self) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        if let Some(ref user_agent) = self.user_agent {
+            let user_agent =
+                HeaderValue::from_str(user_agent).expect("User agent to be a valid header value");
+            headers.insert(USER_AGENT, user_agent);
         }
+        headers
     }
 
-    fn new_backoff(&self) -> ExponentialBackoff {
-        ExponentialBackoff {
-            max_interval: Duration::new(self.max_interval, 0),
-            ..Default::default()
+    fn rate_limiter(&self) -> Option<RateLimiter> {
+        self.rate_limit
+            .map(|(rate, burst)| RateLimiter::new(rate, burst))
+    }
+
+    pub fn build(self) -> Result<Api, Error> {
+        debug!(
+            "Creating API Client for {} at {}",
+            self.version, self.base_url
+        );
+
+        let mut client = Client::builder().default_headers(self.default_headers());
+        if let Some(timeout) = self.timeout {
+            client = client.timeout(timeout);
+        }
+        let rate_limiter = self.rate_limiter();
+
+        Ok(Api {
+            version: self.version,
+            format: self.format,
+            base_url: self.base_url,
+            pacing: self.pacing,
+            fetcher: Fetcher {
+                client: client.build().map_err(Error::Client)?,
+                format: self.format,
+                retry: self.retry,
+                rate_limiter,
+            },
+        })
+    }
+
+    /// Build an `AsyncApi` client instead, to be run on a tokio runtime
+    #[cfg(feature = "async")]
+    pub fn build_async(self) -> Result<AsyncApi, Error> {
+        debug!(
+            "Creating async API Client for {} at {}",
+            self.version, self.base_url
+        );
+
+        let mut client = async::Client::builder().default_headers(self.default_headers());
+        if let Some(timeout) = self.timeout {
+            client = client.timeout(timeout);
+        }
+
+        let url = [
+            self.base_url.as_str(),
+            self.version.as_str(),
+            self.format.to_string().as_str(),
+        ]
+            .join("/");
+
+        Ok(AsyncApi::new(
+            url,
+            self.pacing,
+            client.build().map_err(Error::Client)?,
+            self.format,
+            self.retry,
+            self.rate_limiter(),
+        ))
+    }
+}
+
+impl Default for ApiBuilder {
+    fn default() -> Self {
+        Self {
+            version: DEFAULT_VERSION.to_string(),
+            format: ApiFormat::Json,
+            base_url: DEFAULT_BASE_URL.to_string(),
+            pacing: Default::default(),
+            timeout: None,
+            user_agent: None,
+            retry: Default::default(),
+            rate_limit: None,
         }
     }
+}
+
+impl Api {
+    pub fn new(format: ApiFormat, max_interval: u64) -> Self {
+        Self::builder()
+            .format(format)
+            .max_backoff(max_interval)
+            .build()
+            .expect("Client::new()")
+    }
+
+    pub fn builder() -> ApiBuilder {
+        Default::default()
+    }
 
     fn api_method_url(&self, method: &str) -> String {
         [
@@ -139,37 +700,36 @@ impl Api {
 
     fn paginate_api_lazy<R, T>(&self, base_url: &str) -> PaginatedIterator<R, T>
     where
-        R: DeserializeOwned + PaginatedResult<T>,
+        R: ApiResponse + PaginatedResult<T>,
     {
         PaginatedIterator::<R, T> {
             base_url: base_url.to_string(),
-            client: Client::new(),
+            fetcher: self.fetcher.clone(),
             page_number: 1,
             total_pages: 1,
             page: VecDeque::new(),
-            backoff: self.new_backoff(),
+            pacer: self.pacing.pacer(),
             size_hint: None,
+            on_page: None,
+            until: None,
+            stopped: false,
             _marker: Default::default(),
         }
     }
 
     fn paginate_api<R, T>(&self, base_url: &str) -> Result<Vec<T>, Error>
     where
-        R: DeserializeOwned + PaginatedResult<T>,
+        R: ApiResponse + PaginatedResult<T>,
     {
         let mut page_number = 1;
         let mut total_pages = 1;
         let mut results = vec![];
-        let client = Client::new();
-
-        let mut backoff = self.new_backoff();
+        let mut pacer = self.pacing.pacer();
 
         debug!("Making paginated requests for API {}", base_url);
 
         while page_number <= total_pages {
-            let duration = backoff
-                .next_backoff()
-                .unwrap_or_else(|| Duration::new(0, 0));
+            let duration = pacer.delay();
             debug!(
                 "Sleeping {}.{} seconds before the next request",
                 duration.as_secs(),
@@ -178,7 +738,7 @@ impl Api {
             sleep(duration);
 
             let url = [base_url, format!("{}", page_number).as_str()].join("/");
-            let result: R = client.get(&url).send()?.json()?;
+            let result: R = self.fetcher.get(&url, Some(page_number))?;
             debug!(
                 "\t fetching page {} of {}",
                 result.page(),
@@ -194,20 +754,34 @@ impl Api {
         Ok(results)
     }
 
-    pub fn listings(
-        &self,
-        item_id: u64,
-        listing_type: ListingType,
-    ) -> Result<Vec<data::ItemListing>, Error> {
+    fn listings_url(&self, item_id: u64, listing_type: ListingType) -> String {
         let base_url = self.api_method_url("listings");
-        let base_url = [
+        [
             base_url.as_str(),
             &format!("{}", item_id),
             listing_type.to_string().as_str(),
         ]
-            .join("/");
+            .join("/")
+    }
+
+    pub fn listings(
+        &self,
+        item_id: u64,
+        listing_type: ListingType,
+    ) -> Result<Vec<data::ItemListing>, Error> {
+        self.paginate_api::<ItemListings, data::ItemListing>(
+            &self.listings_url(item_id, listing_type),
+        )
+    }
 
-        self.paginate_api::<ItemListings, data::ItemListing>(&base_url)
+    /// Listings of the item, newest first. Pages are only fetched as they are needed, so
+    /// stopping early, e.g. with `PaginatedIterator::until`, skips the rest of the history.
+    pub fn listings_lazy(
+        &self,
+        item_id: u64,
+        listing_type: ListingType,
+    ) -> PaginatedIterator<ItemListings, data::ItemListing> {
+        self.paginate_api_lazy(&self.listings_url(item_id, listing_type))
     }
 
     pub fn item_search(&self, search: &str) -> Result<Vec<data::Item>, Error> {
@@ -224,29 +798,103 @@ impl Api {
         self.paginate_api_lazy(&base_url)
     }
 
-    pub fn items(&self) -> Result<Vec<data::Item>, Error> {
+    /// Base URL of the items API for `item_type`, which is either a type ID or `all`
+    fn items_url(&self, item_type: &str) -> String {
         let base_url = self.api_method_url("items");
-        let base_url = [base_url.as_str(), "all"].join("/");
+        [base_url.as_str(), item_type].join("/")
+    }
 
-        self.paginate_api::<Items, data::Item>(&base_url)
+    pub fn items(&self) -> Result<Vec<data::Item>, Error> {
+        self.paginate_api::<Items, data::Item>(&self.items_url("all"))
     }
 
     pub fn items_lazy(&self) -> PaginatedIterator<Items, data::Item> {
-        let base_url = self.api_method_url("items");
-        let base_url = [base_url.as_str(), "all"].join("/");
+        self.paginate_api_lazy(&self.items_url("all"))
+    }
 
-        self.paginate_api_lazy(&base_url)
+    /// Items of the type, e.g. 5 for crafting materials. See `types` for the type IDs.
+    pub fn items_by_type(&self, type_id: u64) -> Result<Vec<data::Item>, Error> {
+        self.paginate_api::<Items, data::Item>(&self.items_url(&type_id.to_string()))
+    }
+
+    pub fn items_by_type_lazy(&self, type_id: u64) -> PaginatedIterator<Items, data::Item> {
+        self.paginate_api_lazy(&self.items_url(&type_id.to_string()))
     }
 
     pub fn item(&self, id: u64) -> Result<data::Item, Error> {
         let base_url = self.api_method_url("item");
         let url = [base_url.as_str(), &format!("{}", id)].join("/");
 
-        let client = Client::new();
         debug!("Requesting Item data for ID {}", id);
-        let result: Item = client.get(&url).send()?.json()?;
+        let result: Item = self.fetcher.get(&url, None)?;
         Ok(result.result)
     }
+
+    /// Base URL of the recipes API for the discipline, or every discipline if `None`
+    fn recipes_url(&self, discipline_id: Option<u64>) -> String {
+        let discipline = match discipline_id {
+            Some(id) => id.to_string(),
+            None => "all".to_string(),
+        };
+        let base_url = self.api_method_url("recipes");
+        [base_url.as_str(), discipline.as_str()].join("/")
+    }
+
+    /// Recipes of the crafting discipline, or of every discipline if `None`. See `disciplines`
+    /// for the discipline IDs.
+    pub fn recipes(&self, discipline_id: Option<u64>) -> Result<Vec<data::Recipe>, Error> {
+        self.paginate_api::<Recipes, data::Recipe>(&self.recipes_url(discipline_id))
+    }
+
+    pub fn recipes_lazy(
+        &self,
+        discipline_id: Option<u64>,
+    ) -> PaginatedIterator<Recipes, data::Recipe> {
+        self.paginate_api_lazy(&self.recipes_url(discipline_id))
+    }
+
+    /// Recipe with its ingredients
+    pub fn recipe(&self, id: u64) -> Result<data::Recipe, Error> {
+        let base_url = self.api_method_url("recipe");
+        let url = [base_url.as_str(), &format!("{}", id)].join("/");
+
+        debug!("Requesting Recipe data for ID {}", id);
+        let result: Recipe = self.fetcher.get(&url, None)?;
+        Ok(result.result)
+    }
+
+    /// Current gem exchange rates
+    pub fn gem_price(&self) -> Result<data::GemPrice, Error> {
+        debug!("Requesting gem price");
+        let result: GemPrice = self.fetcher.get(&self.api_method_url("gem-price"), None)?;
+        Ok(result.result)
+    }
+
+    /// Item types, with their sub-types
+    pub fn types(&self) -> Result<Vec<data::ItemType>, Error> {
+        debug!("Requesting item types");
+        let result: Types = self.fetcher.get(&self.api_method_url("types"), None)?;
+        Ok(result.results)
+    }
+
+    /// Resolver for the names of item types, fetched with `types`
+    pub fn type_names(&self) -> Result<data::TypeNames, Error> {
+        self.types().map(|types| data::TypeNames::new(&types))
+    }
+
+    pub fn disciplines(&self) -> Result<Vec<data::Discipline>, Error> {
+        debug!("Requesting crafting disciplines");
+        let result: Results<data::Discipline> =
+            self.fetcher.get(&self.api_method_url("disciplines"), None)?;
+        Ok(result.results)
+    }
+
+    pub fn rarities(&self) -> Result<Vec<data::RarityName>, Error> {
+        debug!("Requesting rarities");
+        let result: Results<data::RarityName> =
+            self.fetcher.get(&self.api_method_url("rarities"), None)?;
+        Ok(result.results)
+    }
 }
 
 impl Default for Api {
@@ -255,7 +903,7 @@ impl Default for Api {
     }
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Eq, PartialEq)]
 #[serde(rename_all = "lowercase")]
 pub enum ListingType {
     Sell,
@@ -279,6 +927,20 @@ pub struct Items {
     pub results: Vec<data::Item>,
 }
 
+impl ApiResponse for Items {
+    type Row = data::Item;
+
+    fn from_rows(rows: Vec<data::Item>, page: Option<u64>) -> Result<Self, DecodeError> {
+        let page = page.unwrap_or(1);
+        Ok(Self {
+            count: rows.len() as u64,
+            page,
+            last_page: page,
+            results: rows,
+        })
+    }
+}
+
 impl PaginatedResult<data::Item> for Items {
     fn page(&self) -> u64 {
         self.page
@@ -299,8 +961,9 @@ impl PaginatedResult<data::Item> for Items {
 
 #[derive(Serialize, Deserialize, Debug)]
 pub struct ItemListings {
+    /// Not part of CSV responses
     #[serde(rename = "sell-or-buy")]
-    pub listing_type: ListingType,
+    pub listing_type: Option<ListingType>,
     pub count: u64,
     pub page: u64,
     pub last_page: u64,
@@ -308,6 +971,22 @@ pub struct ItemListings {
     pub results: Vec<data::ItemListing>,
 }
 
+impl ApiResponse for ItemListings {
+    type Row = data::ItemListing;
+
+    fn from_rows(rows: Vec<data::ItemListing>, page: Option<u64>) -> Result<Self, DecodeError> {
+        let page = page.unwrap_or(1);
+        Ok(Self {
+            listing_type: None,
+            count: rows.len() as u64,
+            page,
+            last_page: page,
+            total: rows.len() as u64,
+            results: rows,
+        })
+    }
+}
+
 impl PaginatedResult<data::ItemListing> for ItemListings {
     fn page(&self) -> u64 {
         self.page
@@ -330,3 +1009,144 @@ impl PaginatedResult<data::ItemListing> for ItemListings {
 pub struct Item {
     pub result: data::Item,
 }
+
+impl ApiResponse for Item {
+    type Row = data::Item;
+
+    fn from_rows(rows: Vec<data::Item>, _page: Option<u64>) -> Result<Self, DecodeError> {
+        let result = rows.into_iter().next().ok_or(DecodeError::Empty)?;
+        Ok(Self { result })
+    }
+}
+
+/// Results of an API that is not paginated
+#[derive(Serialize, Deserialize, Debug)]
+pub struct Results<T> {
+    pub results: Vec<T>,
+}
+
+impl<T> ApiResponse for Results<T>
+where
+    T: DeserializeOwned,
+{
+    type Row = T;
+
+    fn from_rows(rows: Vec<T>, _page: Option<u64>) -> Result<Self, DecodeError> {
+        Ok(Self { results: rows })
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct Types {
+    pub results: Vec<data::ItemType>,
+}
+
+/// Row of the CSV variant of the types API, which flattens sub-types into rows with a
+/// `parent_id`
+#[derive(Serialize, Deserialize, Debug)]
+pub struct TypeRow {
+    pub id: u64,
+    pub name: String,
+    pub parent_id: Option<u64>,
+}
+
+impl ApiResponse for Types {
+    type Row = TypeRow;
+
+    fn from_rows(rows: Vec<TypeRow>, _page: Option<u64>) -> Result<Self, DecodeError> {
+        let (sub_types, types): (Vec<_>, Vec<_>) =
+            rows.into_iter().partition(|row| row.parent_id.is_some());
+
+        let mut results: Vec<data::ItemType> = types
+            .into_iter()
+            .map(|row| data::ItemType {
+                id: row.id,
+                name: row.name,
+                sub_types: vec![],
+            }).collect();
+
+        for row in sub_types {
+            let parent_id = row.parent_id.expect("to be partitioned");
+            match results.iter_mut().find(|item_type| item_type.id == parent_id) {
+                Some(item_type) => item_type.sub_types.push(data::ItemSubType {
+                    id: row.id,
+                    name: row.name,
+                }),
+                None => warn!(
+                    "Ignoring sub-type \"{}\" of unknown type {}",
+                    row.name, parent_id
+                ),
+            }
+        }
+
+        Ok(Self { results })
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct Recipes {
+    pub count: u64,
+    pub page: u64,
+    pub last_page: u64,
+    pub results: Vec<data::Recipe>,
+}
+
+impl ApiResponse for Recipes {
+    type Row = data::Recipe;
+
+    fn from_rows(rows: Vec<data::Recipe>, page: Option<u64>) -> Result<Self, DecodeError> {
+        let page = page.unwrap_or(1);
+        Ok(Self {
+            count: rows.len() as u64,
+            page,
+            last_page: page,
+            results: rows,
+        })
+    }
+}
+
+impl PaginatedResult<data::Recipe> for Recipes {
+    fn page(&self) -> u64 {
+        self.page
+    }
+
+    fn last_page(&self) -> u64 {
+        self.last_page
+    }
+
+    fn results(self) -> Vec<data::Recipe> {
+        self.results
+    }
+
+    fn count(&self) -> Option<usize> {
+        Some((self.count * self.last_page) as usize)
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct Recipe {
+    pub result: data::Recipe,
+}
+
+impl ApiResponse for Recipe {
+    type Row = data::Recipe;
+
+    fn from_rows(rows: Vec<data::Recipe>, _page: Option<u64>) -> Result<Self, DecodeError> {
+        let result = rows.into_iter().next().ok_or(DecodeError::Empty)?;
+        Ok(Self { result })
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct GemPrice {
+    pub result: data::GemPrice,
+}
+
+impl ApiResponse for GemPrice {
+    type Row = data::GemPrice;
+
+    fn from_rows(rows: Vec<data::GemPrice>, _page: Option<u64>) -> Result<Self, DecodeError> {
+        let result = rows.into_iter().next().ok_or(DecodeError::Empty)?;
+        Ok(Self { result })
+    }
+}
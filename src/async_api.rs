@@ -0,0 +1,337 @@
+//! Non-blocking variant of `Api`, built on `futures` and meant to be run on a tokio runtime
+use api::{
+    self, ApiFormat, ApiResponse, Error, Items, ListingType, PaginatedResult, Recipes, Results,
+    Retry,
+};
+use backoff::backoff::Backoff;
+use data;
+use futures::future::{self, Loop};
+use futures::stream;
+use futures::{Future, Stream};
+use pacing::{Pacer, Pacing, RateLimiter};
+use reqwest::async::Client;
+use std::time::{Duration, Instant};
+use tokio::timer::Delay;
+
+/// Results of a paginated API, fetched page by page as the stream is polled
+pub type PaginatedStream<T> = Box<dyn Stream<Item = T, Error = Error> + Send>;
+
+pub type ApiFuture<T> = Box<dyn Future<Item = T, Error = Error> + Send>;
+
+/// Non-blocking client for the GW2Spidy API, created with `ApiBuilder::build_async`
+pub struct AsyncApi {
+    url: String,
+    pacing: Pacing,
+    fetcher: Fetcher,
+}
+
+/// Resolves after `duration`. Timer errors are logged and otherwise ignored.
+fn delay(duration: Duration) -> impl Future<Item = (), Error = Error> {
+    Delay::new(Instant::now() + duration).then(|result| {
+        if let Err(e) = result {
+            warn!("Unable to wait before the next request: {}", e);
+        }
+        Ok(())
+    })
+}
+
+/// Make a GET request and decode the response
+fn get<R>(client: &Client, format: ApiFormat, url: String, page: Option<u64>) -> ApiFuture<R>
+where
+    R: ApiResponse + Send + 'static,
+{
+    let request_url = url.clone();
+    let request_error = move |cause| Error::Request {
+        url: request_url.clone(),
+        page,
+        cause,
+    };
+    let body_error = request_error.clone();
+
+    let result = client
+        .get(&url)
+        .send()
+        .map_err(request_error)
+        .and_then(move |mut response| {
+            let status = response.status();
+            let result: ApiFuture<R> = if status.is_success() {
+                Box::new(
+                    response
+                        .text()
+                        .map_err(body_error)
+                        .and_then(move |body| api::decode(format, &body, &url, page)),
+                )
+            } else {
+                Box::new(future::err(Error::Status {
+                    retry_after: api::retry_after(response.headers()),
+                    url,
+                    page,
+                    status,
+                }))
+            };
+            result
+        });
+    Box::new(result)
+}
+
+/// Makes requests to the API, retrying them according to the `Retry` policy
+#[derive(Clone)]
+struct Fetcher {
+    client: Client,
+    format: ApiFormat,
+    retry: Retry,
+    rate_limiter: Option<RateLimiter>,
+}
+
+impl Fetcher {
+    fn get<R>(&self, url: String, page: Option<u64>) -> ApiFuture<R>
+    where
+        R: ApiResponse + Send + 'static,
+    {
+        let fetcher = self.clone();
+        let backoff = self.retry.new_backoff();
+
+        let result = future::loop_fn((backoff, 1), move |(mut backoff, attempt)| {
+            let rate_limit = match fetcher.rate_limiter {
+                Some(ref rate_limiter) => rate_limiter.reserve(),
+                None => Duration::new(0, 0),
+            };
+            let client = fetcher.client.clone();
+            let format = fetcher.format;
+            let url = url.clone();
+            let max_attempts = fetcher.retry.max_attempts;
+
+            delay(rate_limit)
+                .and_then(move |_| get::<R>(&client, format, url, page).then(Ok::<_, Error>))
+                .and_then(move |result| {
+                    let error = match result {
+                        Ok(result) => return future::Either::A(future::ok(Loop::Break(result))),
+                        Err(e) => e,
+                    };
+
+                    if !error.is_retryable() || attempt >= max_attempts {
+                        return future::Either::A(future::err(error));
+                    }
+
+                    let duration = match backoff.next_backoff() {
+                        Some(duration) => duration.max(error.retry_after().unwrap_or_default()),
+                        None => return future::Either::A(future::err(error)),
+                    };
+                    warn!(
+                        "{}; retrying in {}.{} seconds (attempt {} of {})",
+                        error,
+                        duration.as_secs(),
+                        duration.subsec_millis(),
+                        attempt + 1,
+                        max_attempts
+                    );
+
+                    future::Either::B(
+                        delay(duration).map(move |_| Loop::Continue((backoff, attempt + 1))),
+                    )
+                })
+        });
+        Box::new(result)
+    }
+}
+
+/// Progress of a paginated stream
+struct Pagination {
+    fetcher: Fetcher,
+    base_url: String,
+    page_number: u64,
+    total_pages: u64,
+    pacer: Box<dyn Pacer>,
+}
+
+impl AsyncApi {
+    pub(crate) fn new(
+        url: String,
+        pacing: Pacing,
+        client: Client,
+        format: ApiFormat,
+        retry: Retry,
+        rate_limiter: Option<RateLimiter>,
+    ) -> Self {
+        Self {
+            url,
+            pacing,
+            fetcher: Fetcher {
+                client,
+                format,
+                retry,
+                rate_limiter,
+            },
+        }
+    }
+
+    fn api_method_url(&self, method: &str) -> String {
+        [self.url.as_str(), method].join("/")
+    }
+
+    fn paginate_api<R, T>(&self, base_url: String) -> PaginatedStream<T>
+    where
+        R: ApiResponse + PaginatedResult<T> + Send + 'static,
+        T: Send + 'static,
+    {
+        debug!("Making paginated requests for API {}", base_url);
+
+        let pagination = Pagination {
+            fetcher: self.fetcher.clone(),
+            base_url,
+            page_number: 1,
+            total_pages: 1,
+            pacer: self.pacing.pacer(),
+        };
+
+        let pages = stream::unfold(pagination, |mut pagination| {
+            if pagination.page_number > pagination.total_pages {
+                // We are done
+                return None;
+            }
+
+            let duration = pagination.pacer.delay();
+            debug!(
+                "Sleeping {}.{} seconds before the next request",
+                duration.as_secs(),
+                duration.subsec_millis()
+            );
+
+            let page = pagination.page_number;
+            let url = [pagination.base_url.as_str(), &format!("{}", page)].join("/");
+            let result = delay(duration)
+                .and_then({
+                    let fetcher = pagination.fetcher.clone();
+                    move |_| fetcher.get::<R>(url, Some(page))
+                }).map(move |result| {
+                    debug!("\t Page {} of {}", result.page(), result.last_page());
+
+                    pagination.total_pages = result.last_page();
+                    pagination.page_number = result.page() + 1;
+
+                    (result.results(), pagination)
+                });
+            Some(result)
+        });
+
+        Box::new(pages.map(stream::iter_ok).flatten())
+    }
+
+    pub fn listings(
+        &self,
+        item_id: u64,
+        listing_type: ListingType,
+    ) -> PaginatedStream<data::ItemListing> {
+        let base_url = self.api_method_url("listings");
+        let base_url = [
+            base_url.as_str(),
+            &format!("{}", item_id),
+            listing_type.to_string().as_str(),
+        ]
+            .join("/");
+
+        self.paginate_api::<api::ItemListings, data::ItemListing>(base_url)
+    }
+
+    pub fn item_search(&self, search: &str) -> PaginatedStream<data::Item> {
+        let base_url = self.api_method_url("item-search");
+        let base_url = [base_url.as_str(), search].join("/");
+
+        self.paginate_api::<Items, data::Item>(base_url)
+    }
+
+    pub fn items(&self) -> PaginatedStream<data::Item> {
+        let base_url = self.api_method_url("items");
+        let base_url = [base_url.as_str(), "all"].join("/");
+
+        self.paginate_api::<Items, data::Item>(base_url)
+    }
+
+    pub fn items_by_type(&self, type_id: u64) -> PaginatedStream<data::Item> {
+        let base_url = self.api_method_url("items");
+        let base_url = [base_url.as_str(), &format!("{}", type_id)].join("/");
+
+        self.paginate_api::<Items, data::Item>(base_url)
+    }
+
+    pub fn item(&self, id: u64) -> ApiFuture<data::Item> {
+        let base_url = self.api_method_url("item");
+        let url = [base_url.as_str(), &format!("{}", id)].join("/");
+
+        debug!("Requesting Item data for ID {}", id);
+        Box::new(
+            self.fetcher
+                .get::<api::Item>(url, None)
+                .map(|result| result.result),
+        )
+    }
+
+    /// Recipes of the crafting discipline, or of every discipline if `None`. See `disciplines`
+    /// for the discipline IDs.
+    pub fn recipes(&self, discipline_id: Option<u64>) -> PaginatedStream<data::Recipe> {
+        let discipline = match discipline_id {
+            Some(id) => id.to_string(),
+            None => "all".to_string(),
+        };
+        let base_url = self.api_method_url("recipes");
+        let base_url = [base_url.as_str(), discipline.as_str()].join("/");
+
+        self.paginate_api::<Recipes, data::Recipe>(base_url)
+    }
+
+    /// Recipe with its ingredients
+    pub fn recipe(&self, id: u64) -> ApiFuture<data::Recipe> {
+        let base_url = self.api_method_url("recipe");
+        let url = [base_url.as_str(), &format!("{}", id)].join("/");
+
+        debug!("Requesting Recipe data for ID {}", id);
+        Box::new(
+            self.fetcher
+                .get::<api::Recipe>(url, None)
+                .map(|result| result.result),
+        )
+    }
+
+    /// Current gem exchange rates
+    pub fn gem_price(&self) -> ApiFuture<data::GemPrice> {
+        debug!("Requesting gem price");
+        Box::new(
+            self.fetcher
+                .get::<api::GemPrice>(self.api_method_url("gem-price"), None)
+                .map(|result| result.result),
+        )
+    }
+
+    /// Item types, with their sub-types
+    pub fn types(&self) -> ApiFuture<Vec<data::ItemType>> {
+        debug!("Requesting item types");
+        Box::new(
+            self.fetcher
+                .get::<api::Types>(self.api_method_url("types"), None)
+                .map(|result| result.results),
+        )
+    }
+
+    /// Resolver for the names of item types, fetched with `types`
+    pub fn type_names(&self) -> ApiFuture<data::TypeNames> {
+        Box::new(self.types().map(|types| data::TypeNames::new(&types)))
+    }
+
+    pub fn disciplines(&self) -> ApiFuture<Vec<data::Discipline>> {
+        debug!("Requesting crafting disciplines");
+        Box::new(
+            self.fetcher
+                .get::<Results<data::Discipline>>(self.api_method_url("disciplines"), None)
+                .map(|result| result.results),
+        )
+    }
+
+    pub fn rarities(&self) -> ApiFuture<Vec<data::RarityName>> {
+        debug!("Requesting rarities");
+        Box::new(
+            self.fetcher
+                .get::<Results<data::RarityName>>(self.api_method_url("rarities"), None)
+                .map(|result| result.results),
+        )
+    }
+}
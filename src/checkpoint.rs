@@ -0,0 +1,177 @@
+//! Checkpoint of a run, so that it can be resumed after a crash
+//!
+//! The checkpoint is an append-only JSON Lines file in the output directory. Each line records
+//! either an item whose listings have been written, or the last page fetched by a paginated
+//! iterator.
+use failure;
+use serde_json;
+use std::collections::{HashMap, HashSet};
+use std::fs::{File, OpenOptions};
+use std::io::{BufRead, BufReader, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+
+pub const FILENAME: &str = "checkpoint.jsonl";
+
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(tag = "type", rename_all = "lowercase")]
+enum Entry {
+    Item { id: u64 },
+    Page { url: String, page: u64 },
+}
+
+pub struct Checkpoint {
+    path: PathBuf,
+    /// `None` for a read-only checkpoint
+    file: Option<File>,
+    completed: HashSet<u64>,
+    pages: HashMap<String, u64>,
+    /// Whether an item has failed in this run
+    failed: bool,
+}
+
+impl Checkpoint {
+    /// Start a new checkpoint in the output directory, discarding any existing one
+    pub fn create(output: &Path) -> Result<Self, failure::Error> {
+        let path = output.join(FILENAME);
+        let file = File::create(&path)?;
+
+        Ok(Self {
+            path,
+            file: Some(file),
+            completed: Default::default(),
+            pages: Default::default(),
+            failed: false,
+        })
+    }
+
+    /// Load the checkpoint from the output directory, if any, and continue appending to it
+    pub fn resume(output: &Path) -> Result<Self, failure::Error> {
+        let mut checkpoint = Self::load(output)?;
+
+        let mut file = OpenOptions::new()
+            .create(true)
+            .read(true)
+            .append(true)
+            .open(&checkpoint.path)?;
+
+        // Terminate any truncated line so that it does not corrupt the next entry
+        let length = file.metadata()?.len();
+        if length > 0 {
+            let mut last = [0u8];
+            file.seek(SeekFrom::Start(length - 1))?;
+            file.read_exact(&mut last)?;
+            if last[0] != b'\n' {
+                file.write_all(b"\n")?;
+            }
+        }
+
+        checkpoint.file = Some(file);
+        Ok(checkpoint)
+    }
+
+    /// Checkpoint that records progress in memory only, leaving the output directory untouched.
+    /// The existing checkpoint, if any, is loaded when `resume` is set.
+    pub fn read_only(output: &Path, resume: bool) -> Result<Self, failure::Error> {
+        if resume {
+            return Self::load(output);
+        }
+
+        Ok(Self {
+            path: output.join(FILENAME),
+            file: None,
+            completed: Default::default(),
+            pages: Default::default(),
+            failed: false,
+        })
+    }
+
+    fn load(output: &Path) -> Result<Self, failure::Error> {
+        let path = output.join(FILENAME);
+        let mut completed = HashSet::new();
+        let mut pages = HashMap::new();
+
+        if path.exists() {
+            let reader = BufReader::new(File::open(&path)?);
+            for (number, line) in reader.lines().enumerate() {
+                let line = line?;
+                match serde_json::from_str(&line) {
+                    Ok(Entry::Item { id }) => {
+                        completed.insert(id);
+                    }
+                    Ok(Entry::Page { url, page }) => {
+                        pages.insert(url, page);
+                    }
+                    // Most likely a line that was being written when the process died
+                    Err(e) => warn!(
+                        "Ignoring invalid checkpoint line {} in \"{}\": {}",
+                        number + 1,
+                        path.to_str().unwrap_or("unknown"),
+                        e
+                    ),
+                }
+            }
+            info!(
+                "Resuming from checkpoint with {} completed items",
+                completed.len()
+            );
+        } else {
+            warn!("No checkpoint found; starting from scratch");
+        }
+
+        Ok(Self {
+            path,
+            file: None,
+            completed,
+            pages,
+            failed: false,
+        })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Whether the listings for the item have been written
+    pub fn is_completed(&self, id: u64) -> bool {
+        self.completed.contains(&id)
+    }
+
+    /// Last page fetched for the paginated API at `url`
+    pub fn page(&self, url: &str) -> Option<u64> {
+        self.pages.get(url).cloned()
+    }
+
+    pub fn complete(&mut self, id: u64) -> Result<(), failure::Error> {
+        self.append(&Entry::Item { id })?;
+        self.completed.insert(id);
+        Ok(())
+    }
+
+    /// Record that the listings of an item could not be written. Pages are no longer recorded
+    /// from then on, so that a resumed run lists the item again.
+    pub fn fail(&mut self) {
+        self.failed = true;
+    }
+
+    pub fn set_page(&mut self, url: &str, page: u64) -> Result<(), failure::Error> {
+        if self.failed {
+            return Ok(());
+        }
+        self.append(&Entry::Page {
+            url: url.to_string(),
+            page,
+        })?;
+        self.pages.insert(url.to_string(), page);
+        Ok(())
+    }
+
+    fn append(&mut self, entry: &Entry) -> Result<(), failure::Error> {
+        let mut line = serde_json::to_vec(entry)?;
+        line.push(b'\n');
+        if let Some(ref mut file) = self.file {
+            file.write_all(&line)?;
+            file.flush()?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,327 @@
+//! Cost and profit of crafting recipes, from trading post prices
+use data::{Item, Recipe};
+use std::cmp::Reverse;
+use std::collections::{HashMap, HashSet};
+
+/// Trading post fee on sales, in percent: a 5% listing fee and a 10% exchange fee
+pub const TRADING_POST_FEE: u64 = 15;
+
+/// How items are bought and sold on the trading post
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Trade {
+    /// Buy from the cheapest sell listing, or sell to the highest buy order
+    Instant,
+    /// Place a buy order at the highest buy order, or list at the cheapest sell listing
+    Order,
+}
+
+impl Trade {
+    fn buy_price(self, item: &Item) -> u64 {
+        match self {
+            Trade::Instant => item.min_sale_unit_price,
+            Trade::Order => item.max_offer_unit_price,
+        }
+    }
+
+    fn sell_price(self, item: &Item) -> u64 {
+        match self {
+            Trade::Instant => item.max_offer_unit_price,
+            Trade::Order => item.min_sale_unit_price,
+        }
+    }
+}
+
+/// Cheapest way of obtaining an item, with the cost per unit in copper
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum Source {
+    Buy(u64),
+    Craft(u64),
+}
+
+impl Source {
+    fn cost(self) -> u64 {
+        match self {
+            Source::Buy(cost) | Source::Craft(cost) => cost,
+        }
+    }
+}
+
+/// Profit of crafting a recipe and selling the result
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Profit<'a> {
+    pub recipe: &'a Recipe,
+    /// Cost of the ingredients, in copper
+    pub cost: u64,
+    /// Sale price of the crafted items after the trading post fee, in copper
+    pub revenue: u64,
+    pub profit: i64,
+    /// Ingredients that are cheaper to craft than to buy
+    pub crafted: Vec<u64>,
+}
+
+/// Computes crafting profits, choosing for every ingredient whether to craft or buy it
+pub struct Calculator<'a> {
+    items: HashMap<u64, &'a Item>,
+    recipes: HashMap<u64, Vec<&'a Recipe>>,
+    buy: Trade,
+    sell: Trade,
+    sources: HashMap<u64, Option<Source>>,
+    /// Whether a cycle was cut short since the last call to `source`
+    cut: bool,
+}
+
+impl<'a> Calculator<'a> {
+    /// Recipes need their ingredients, which are only returned by `Api::recipe`
+    pub fn new(items: &'a [Item], recipes: &'a [Recipe], buy: Trade, sell: Trade) -> Self {
+        let mut by_result: HashMap<u64, Vec<&Recipe>> = HashMap::new();
+        for recipe in recipes {
+            by_result
+                .entry(recipe.result_item_id)
+                .or_default()
+                .push(recipe);
+        }
+
+        Self {
+            items: items.iter().map(|item| (item.id, item)).collect(),
+            recipes: by_result,
+            buy,
+            sell,
+            sources: HashMap::new(),
+            cut: false,
+        }
+    }
+
+    /// Profit of crafting the recipe, or `None` if an ingredient cannot be obtained or the result
+    /// cannot be sold
+    pub fn profit(&mut self, recipe: &'a Recipe) -> Option<Profit<'a>> {
+        if recipe.ingredients.is_empty() {
+            return None;
+        }
+        let sell_price = self
+            .items
+            .get(&recipe.result_item_id)
+            .map(|item| self.sell.sell_price(item))
+            .filter(|price| *price > 0)?;
+
+        let mut visiting = HashSet::new();
+        visiting.insert(recipe.result_item_id);
+        let mut cost = 0;
+        let mut crafted = vec![];
+        for ingredient in &recipe.ingredients {
+            let source = self.source(ingredient.item_id, &mut visiting)?;
+            if let Source::Craft(_) = source {
+                crafted.push(ingredient.item_id);
+            }
+            cost += source.cost() * ingredient.count;
+        }
+
+        let revenue = sell_price * recipe.result_count * (100 - TRADING_POST_FEE) / 100;
+        Some(Profit {
+            recipe,
+            cost,
+            revenue,
+            profit: revenue as i64 - cost as i64,
+            crafted,
+        })
+    }
+
+    /// Profitable recipes, most profitable first
+    pub fn ranked(&mut self, recipes: &'a [Recipe]) -> Vec<Profit<'a>> {
+        let mut profits: Vec<Profit> = recipes
+            .iter()
+            .filter_map(|recipe| self.profit(recipe))
+            .filter(|profit| profit.profit > 0)
+            .collect();
+        profits.sort_by_key(|profit| Reverse(profit.profit));
+        profits
+    }
+
+    /// Cheapest way of obtaining one unit of the item. Items that are currently being crafted
+    /// further up are never crafted again, to break cycles between recipes.
+    fn source(&mut self, item_id: u64, visiting: &mut HashSet<u64>) -> Option<Source> {
+        if let Some(source) = self.sources.get(&item_id) {
+            return *source;
+        }
+
+        let buy = self
+            .items
+            .get(&item_id)
+            .map(|item| self.buy.buy_price(item))
+            .filter(|price| *price > 0)
+            .map(Source::Buy);
+
+        let cut = self.cut;
+        self.cut = false;
+        let mut craft = None;
+        if visiting.insert(item_id) {
+            let recipes = self.recipes.get(&item_id).cloned().unwrap_or_default();
+            for recipe in recipes {
+                if let Some(cost) = self.unit_cost(recipe, visiting) {
+                    craft = match craft {
+                        Some(Source::Craft(cheapest)) if cheapest <= cost => craft,
+                        _ => Some(Source::Craft(cost)),
+                    };
+                }
+            }
+            visiting.remove(&item_id);
+        } else {
+            self.cut = true;
+        }
+
+        let source = match (buy, craft) {
+            (Some(buy), Some(craft)) if craft.cost() < buy.cost() => Some(craft),
+            (Some(buy), _) => Some(buy),
+            (None, craft) => craft,
+        };
+        // Answers from a cycle that was cut short depend on where the cycle was entered
+        if !self.cut {
+            self.sources.insert(item_id, source);
+        }
+        self.cut |= cut;
+        source
+    }
+
+    /// Cost of crafting one unit of the recipe's result, rounded up
+    fn unit_cost(&mut self, recipe: &Recipe, visiting: &mut HashSet<u64>) -> Option<u64> {
+        if recipe.ingredients.is_empty() || recipe.result_count == 0 {
+            return None;
+        }
+
+        let mut cost = 0;
+        for ingredient in &recipe.ingredients {
+            cost += self.source(ingredient.item_id, visiting)?.cost() * ingredient.count;
+        }
+        Some(cost.div_ceil(recipe.result_count))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Utc;
+    use data::{Ingredient, Rarity};
+
+    /// Item that can be bought instantly for `sale` and sold instantly for `offer`
+    fn item(id: u64, offer: u64, sale: u64) -> Item {
+        Item {
+            id,
+            name: format!("Item {}", id),
+            rarity: Rarity::Fine,
+            restriction_level: 0,
+            img: String::new(),
+            price_last_changed: Utc::now(),
+            max_offer_unit_price: offer,
+            min_sale_unit_price: sale,
+            offer_availability: 0,
+            sale_availability: 0,
+            sale_price_change_last_hour: 0,
+            offer_price_change_last_hour: 0,
+            type_id: 0,
+            sub_type_id: 0,
+        }
+    }
+
+    fn recipe(
+        id: u64,
+        result_item_id: u64,
+        result_count: u64,
+        ingredients: &[(u64, u64)],
+    ) -> Recipe {
+        Recipe {
+            id,
+            name: format!("Recipe {}", id),
+            discipline_id: 1,
+            result_item_id,
+            result_count,
+            crafting_cost: 0,
+            max_offer_unit_price: 0,
+            min_sale_unit_price: 0,
+            rating: 0,
+            ingredients: ingredients
+                .iter()
+                .map(|&(item_id, count)| Ingredient { item_id, count })
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn subtracts_trading_post_fee() {
+        let items = vec![item(1, 10, 12), item(2, 100, 120)];
+        let recipes = vec![recipe(1, 2, 1, &[(1, 5)])];
+        let mut calculator = Calculator::new(&items, &recipes, Trade::Instant, Trade::Instant);
+
+        let profit = calculator.profit(&recipes[0]).unwrap();
+        assert_eq!(profit.cost, 60);
+        assert_eq!(profit.revenue, 85);
+        assert_eq!(profit.profit, 25);
+    }
+
+    #[test]
+    fn uses_order_prices() {
+        let items = vec![item(1, 10, 12), item(2, 100, 120)];
+        let recipes = vec![recipe(1, 2, 1, &[(1, 5)])];
+        let mut calculator = Calculator::new(&items, &recipes, Trade::Order, Trade::Order);
+
+        let profit = calculator.profit(&recipes[0]).unwrap();
+        assert_eq!(profit.cost, 50);
+        assert_eq!(profit.revenue, 102);
+    }
+
+    #[test]
+    fn crafts_ingredients_when_cheaper() {
+        // Item 2 costs 100 to buy, but 2 * 30 / 2 = 30 each to craft from item 1
+        let items = vec![item(1, 20, 30), item(2, 90, 100), item(3, 1000, 1100)];
+        let recipes = vec![recipe(1, 2, 2, &[(1, 2)]), recipe(2, 3, 1, &[(2, 3)])];
+        let mut calculator = Calculator::new(&items, &recipes, Trade::Instant, Trade::Instant);
+
+        let profit = calculator.profit(&recipes[1]).unwrap();
+        assert_eq!(profit.cost, 90);
+        assert_eq!(profit.crafted, vec![2]);
+    }
+
+    #[test]
+    fn crafts_ingredients_that_cannot_be_bought() {
+        let items = vec![item(1, 20, 30), item(3, 1000, 1100)];
+        let recipes = vec![recipe(1, 2, 1, &[(1, 2)]), recipe(2, 3, 1, &[(2, 1)])];
+        let mut calculator = Calculator::new(&items, &recipes, Trade::Instant, Trade::Instant);
+
+        assert_eq!(calculator.profit(&recipes[1]).unwrap().cost, 60);
+    }
+
+    #[test]
+    fn breaks_cycles_between_recipes() {
+        // Items 1 and 2 can be crafted from each other, and neither can be bought
+        let items = vec![item(1, 0, 0), item(2, 0, 0), item(3, 100, 120)];
+        let recipes = vec![
+            recipe(1, 1, 1, &[(2, 1)]),
+            recipe(2, 2, 1, &[(1, 1)]),
+            recipe(3, 3, 1, &[(1, 1)]),
+        ];
+        let mut calculator = Calculator::new(&items, &recipes, Trade::Instant, Trade::Instant);
+
+        assert_eq!(calculator.profit(&recipes[2]), None);
+    }
+
+    #[test]
+    fn ranks_profitable_recipes() {
+        let items = vec![
+            item(1, 10, 10),
+            item(2, 100, 120),
+            item(3, 200, 220),
+            item(4, 5, 5),
+        ];
+        let recipes = vec![
+            recipe(1, 2, 1, &[(1, 5)]),
+            recipe(2, 3, 1, &[(1, 5)]),
+            recipe(3, 4, 1, &[(1, 5)]),
+        ];
+        let mut calculator = Calculator::new(&items, &recipes, Trade::Instant, Trade::Instant);
+
+        let ranked: Vec<u64> = calculator
+            .ranked(&recipes)
+            .iter()
+            .map(|profit| profit.recipe.id)
+            .collect();
+        assert_eq!(ranked, vec![2, 1]);
+    }
+}
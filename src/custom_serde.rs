@@ -1,3 +1,28 @@
+use std::any::TypeId;
+use std::cell::Cell;
+
+/// Value that could not be deserialized by `enum_number!` or `timestamp`
+///
+/// Serde errors only carry a message, so the value is recorded on the side to let decoding
+/// errors be classified without parsing the message.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub(crate) enum Invalid {
+    /// Unknown value of the `enum_number!` enum with this type
+    Variant(TypeId),
+    Timestamp,
+}
+
+thread_local!(static INVALID: Cell<Option<Invalid>> = const { Cell::new(None) });
+
+pub(crate) fn set_invalid(invalid: Invalid) {
+    INVALID.with(|cell| cell.set(Some(invalid)));
+}
+
+/// Take the value recorded by the last failed deserialization on this thread, if any
+pub(crate) fn take_invalid() -> Option<Invalid> {
+    INVALID.with(|cell| cell.take())
+}
+
 // From https://serde.rs/enum-number.html
 macro_rules! enum_number {
     ($name:ident { $($variant:ident = $value:expr, )* }) => {
@@ -8,6 +33,14 @@ macro_rules! enum_number {
             $($variant = $value,)*
         }
 
+        impl fmt::Display for $name {
+            fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                match *self {
+                    $( $name::$variant => f.write_str(stringify!($variant)), )*
+                }
+            }
+        }
+
         impl ::serde::Serialize for $name {
             fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
             where
@@ -40,9 +73,12 @@ macro_rules! enum_number {
                         // number to an enum, so use a big `match`.
                         match value {
                             $( $value => Ok($name::$variant), )*
-                            _ => Err(E::custom(
-                                format!("unknown {} value: {}",
-                                stringify!($name), value))),
+                            _ => {
+                                ::custom_serde::set_invalid(::custom_serde::Invalid::Variant(
+                                    ::std::any::TypeId::of::<$name>()));
+                                Err(E::custom(
+                                    format!("unknown {} value: {}", stringify!($name), value)))
+                            }
                         }
                     }
                 }
@@ -54,7 +90,7 @@ macro_rules! enum_number {
     }
 }
 
-pub(crate) mod timestamp {
+pub mod timestamp {
     use chrono::{DateTime, TimeZone, Utc};
     use serde::{self, Deserialize, Deserializer, Serializer};
 
@@ -74,7 +110,9 @@ pub(crate) mod timestamp {
         D: Deserializer<'de>,
     {
         let s = String::deserialize(deserializer)?;
-        Utc.datetime_from_str(&s, FORMAT)
-            .map_err(serde::de::Error::custom)
+        Utc.datetime_from_str(&s, FORMAT).map_err(|e| {
+            ::custom_serde::set_invalid(::custom_serde::Invalid::Timestamp);
+            serde::de::Error::custom(format!("invalid timestamp \"{}\": {}", s, e))
+        })
     }
 }
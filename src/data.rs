@@ -1,4 +1,5 @@
 use chrono::{DateTime, Utc};
+use std::collections::HashMap;
 use std::hash::{Hash, Hasher};
 
 // Hard-coded from https://www.gw2spidy.com/api/v0.9/json/rarities
@@ -31,7 +32,7 @@ pub struct Item {
     pub sale_price_change_last_hour: i32,
     pub offer_price_change_last_hour: i32,
 
-    // TODO
+    /// Use `TypeNames` to look up the names of the type and sub-type
     pub type_id: u64,
     pub sub_type_id: u64,
 }
@@ -42,7 +43,7 @@ impl Hash for Item {
     }
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone, Eq, PartialEq)]
 pub struct ItemListing {
     #[serde(
         rename = "listing_datetime",
@@ -54,3 +55,132 @@ pub struct ItemListing {
     pub quantity: u64,
     pub listings: u64,
 }
+
+#[derive(Serialize, Deserialize, Debug, Clone, Eq, PartialEq)]
+pub struct ItemType {
+    pub id: u64,
+    pub name: String,
+    #[serde(rename = "subtypes")]
+    pub sub_types: Vec<ItemSubType>,
+}
+
+/// Sub-type of an `ItemType`. IDs are only unique within their type.
+#[derive(Serialize, Deserialize, Debug, Clone, Eq, PartialEq)]
+pub struct ItemSubType {
+    pub id: u64,
+    pub name: String,
+}
+
+/// Crafting discipline
+#[derive(Serialize, Deserialize, Debug, Clone, Eq, PartialEq)]
+pub struct Discipline {
+    pub id: u64,
+    pub name: String,
+}
+
+/// Name of a `Rarity`, as returned by the rarities API
+#[derive(Serialize, Deserialize, Debug, Clone, Eq, PartialEq)]
+pub struct RarityName {
+    pub id: u64,
+    pub name: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Eq, PartialEq)]
+pub struct Recipe {
+    #[serde(rename = "data_id")]
+    pub id: u64,
+    pub name: String,
+    pub discipline_id: u64,
+    #[serde(rename = "result_item_data_id")]
+    pub result_item_id: u64,
+    /// Number of result items crafted at once
+    pub result_count: u64,
+    /// Cost of buying the ingredients, in copper
+    pub crafting_cost: u64,
+    #[serde(rename = "result_item_max_offer_unit_price")]
+    pub max_offer_unit_price: u64,
+    #[serde(rename = "result_item_min_sale_unit_price")]
+    pub min_sale_unit_price: u64,
+    pub rating: u64,
+    /// Only returned when requesting a single recipe
+    #[serde(default)]
+    pub ingredients: Vec<Ingredient>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Eq, PartialEq)]
+pub struct Ingredient {
+    pub item_id: u64,
+    pub count: u64,
+}
+
+/// Gem exchange rates, in copper
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Eq, PartialEq)]
+pub struct GemPrice {
+    /// Rate when exchanging gems for gold
+    pub gem_to_gold: u64,
+    /// Rate when exchanging gold for gems
+    pub gold_to_gem: u64,
+}
+
+/// Resolves `Item::type_id` and `Item::sub_type_id` to names
+#[derive(Clone, Debug, Default)]
+pub struct TypeNames {
+    types: HashMap<u64, String>,
+    sub_types: HashMap<(u64, u64), String>,
+}
+
+impl TypeNames {
+    pub fn new(types: &[ItemType]) -> Self {
+        let mut names = Self::default();
+        for item_type in types {
+            names.types.insert(item_type.id, item_type.name.clone());
+            for sub_type in &item_type.sub_types {
+                names
+                    .sub_types
+                    .insert((item_type.id, sub_type.id), sub_type.name.clone());
+            }
+        }
+        names
+    }
+
+    pub fn type_name(&self, type_id: u64) -> Option<&str> {
+        self.types.get(&type_id).map(String::as_str)
+    }
+
+    pub fn sub_type_name(&self, type_id: u64, sub_type_id: u64) -> Option<&str> {
+        self.sub_types
+            .get(&(type_id, sub_type_id))
+            .map(String::as_str)
+    }
+
+    /// ID of the type with the given name, ignoring case
+    pub fn find_type(&self, name: &str) -> Option<u64> {
+        self.types
+            .iter()
+            .find(|(_, type_name)| type_name.eq_ignore_ascii_case(name))
+            .map(|(id, _)| *id)
+    }
+
+    /// ID of the sub-type of `type_id` with the given name, ignoring case
+    pub fn find_sub_type(&self, type_id: u64, name: &str) -> Option<u64> {
+        self.sub_types
+            .iter()
+            .find(|((parent_id, _), sub_type_name)| {
+                *parent_id == type_id && sub_type_name.eq_ignore_ascii_case(name)
+            }).map(|((_, id), _)| *id)
+    }
+
+    /// Type and sub-type of the item, e.g. "Weapon / Greatsword". Unknown types are shown by ID,
+    /// and unknown sub-types are left out.
+    pub fn describe(&self, item: &Item) -> String {
+        let type_name = match self.type_name(item.type_id) {
+            Some(name) => name.to_string(),
+            None => format!("Type {}", item.type_id),
+        };
+
+        match self.sub_type_name(item.type_id, item.sub_type_id) {
+            Some(sub_type_name) => format!("{} / {}", type_name, sub_type_name),
+            None => type_name,
+        }
+    }
+}
@@ -0,0 +1,100 @@
+//! `gems` subcommand, which appends the gem exchange rates to a CSV file
+use chrono::{DateTime, Utc};
+use clap::{App, Arg, ArgMatches, SubCommand};
+use csv;
+use failure;
+use spidy_scrapey::{api, data};
+use std::fs::OpenOptions;
+use std::thread::sleep;
+use std::time::Duration;
+
+pub const FILENAME: &str = "gems.csv";
+
+#[derive(Serialize, Debug)]
+struct GemPriceOutput {
+    #[serde(with = "::spidy_scrapey::custom_serde::timestamp")]
+    timestamp: DateTime<Utc>,
+    gem_to_gold: u64,
+    gold_to_gem: u64,
+}
+
+impl GemPriceOutput {
+    fn from_gem_price(timestamp: DateTime<Utc>, gem_price: &data::GemPrice) -> Self {
+        Self {
+            timestamp,
+            gem_to_gold: gem_price.gem_to_gold,
+            gold_to_gem: gem_price.gold_to_gem,
+        }
+    }
+}
+
+pub fn subcommand<'a, 'b>() -> App<'a, 'b>
+where
+    'a: 'b,
+{
+    SubCommand::with_name("gems")
+        .about("Append the gem exchange rates to a CSV file in the output directory")
+        .arg(
+            Arg::with_name("interval")
+                .help("Poll the exchange rates every INTERVAL seconds instead of once")
+                .long("--interval")
+                .value_name("INTERVAL")
+                .takes_value(true),
+        ).arg(
+            Arg::with_name("count")
+                .help("Stop after polling this many times. Defaults to polling forever.")
+                .long("--count")
+                .takes_value(true)
+                .requires("interval"),
+        )
+}
+
+pub fn run(api: &api::Api, args: &ArgMatches) -> Result<(), failure::Error> {
+    let interval = args
+        .value_of("interval")
+        .map(|_| Duration::from_secs(value_t!(args, "interval", u64).unwrap_or_else(|e| e.exit())));
+    let count = match (interval, args.value_of("count")) {
+        (None, _) => Some(1),
+        (Some(_), Some(_)) => Some(value_t!(args, "count", u64).unwrap_or_else(|e| e.exit())),
+        (Some(_), None) => None,
+    };
+
+    let path = ::output_dir(args)?.join(FILENAME);
+    let file = OpenOptions::new().create(true).append(true).open(&path)?;
+    let is_empty = file.metadata()?.len() == 0;
+    let mut wtr = csv::WriterBuilder::new()
+        .has_headers(is_empty)
+        .from_writer(file);
+
+    info!(
+        "Appending gem prices to \"{}\"",
+        path.to_str().unwrap_or("unknown")
+    );
+
+    let mut polls = 0;
+    loop {
+        let timestamp = Utc::now();
+        match api.gem_price() {
+            Ok(gem_price) => {
+                info!(
+                    "Gem to gold: {}, gold to gem: {}",
+                    gem_price.gem_to_gold, gem_price.gold_to_gem
+                );
+                wtr.serialize(GemPriceOutput::from_gem_price(timestamp, &gem_price))?;
+                wtr.flush()?;
+            }
+            // A single poll has nothing to fall back on
+            Err(e) if interval.is_none() => return Err(e.into()),
+            Err(e) => warn!("Unable to fetch gem price: {}", e),
+        }
+
+        polls += 1;
+        match (interval, count) {
+            (_, Some(count)) if polls >= count => break,
+            (Some(interval), _) => sleep(interval),
+            (None, _) => break,
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,41 @@
+//! Client for the [GW2Spidy](https://www.gw2spidy.com/) API
+//!
+//! Use `Api` for blocking requests, or `AsyncApi` to run on a tokio runtime. Both are created
+//! with `Api::builder()`. `AsyncApi` needs the `async` feature, which is enabled by default.
+#[macro_use]
+extern crate log;
+#[macro_use]
+extern crate serde_derive;
+
+extern crate backoff;
+extern crate chrono;
+extern crate csv;
+extern crate failure;
+#[cfg(feature = "async")]
+extern crate futures;
+extern crate reqwest;
+extern crate serde;
+extern crate serde_json;
+#[cfg(feature = "async")]
+extern crate tokio;
+
+#[macro_use]
+pub mod custom_serde;
+pub mod api;
+#[cfg(feature = "async")]
+pub mod async_api;
+pub mod crafting;
+pub mod data;
+pub mod pacing;
+
+pub use api::{
+    Api, ApiBuilder, ApiFormat, ApiResponse, DecodeError, Error, ListingType, PaginatedIterator,
+    PaginatedResult, Retry,
+};
+#[cfg(feature = "async")]
+pub use async_api::{AsyncApi, PaginatedStream};
+pub use data::{
+    Discipline, GemPrice, Ingredient, Item, ItemListing, ItemSubType, ItemType, Rarity, RarityName,
+    Recipe, TypeNames,
+};
+pub use pacing::Pacing;
@@ -0,0 +1,557 @@
+//! `listings` subcommand, which downloads the listing history of items
+use checkpoint::Checkpoint;
+use clap::{App, Arg, ArgMatches, SubCommand};
+use failure;
+use itertools::Itertools;
+use manifest::{self, Manifest};
+use output::{FilenameTemplate, Format, ListingOutput, OutputSink, Written};
+use reqwest;
+use spidy_scrapey::{api, data};
+use std::fmt;
+use std::ops::Deref;
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+use window::Window;
+use workers::{Idle, WorkerPool};
+
+/// "Total" count
+#[derive(Clone, Copy)]
+pub struct Total(Option<usize>);
+
+impl Deref for Total {
+    type Target = Option<usize>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl Default for Total {
+    fn default() -> Self {
+        Total(None)
+    }
+}
+
+impl fmt::Display for Total {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.0 {
+            None => write!(f, "unknown"),
+            Some(count) => write!(f, "{}", count),
+        }
+    }
+}
+
+impl From<Option<usize>> for Total {
+    fn from(count: Option<usize>) -> Self {
+        Total(count)
+    }
+}
+
+pub fn subcommand<'a, 'b>() -> App<'a, 'b>
+where
+    'a: 'b,
+{
+    SubCommand::with_name("listings")
+        .about(
+            "Fetch price listing data for items to the output directory. \
+             Specify items by IDs, their names or their type.",
+        ).arg(
+            Arg::with_name("item_id")
+                .help("Item ID to fetch pricing data for")
+                .required(true)
+                .multiple(true)
+                .takes_value(true)
+                .number_of_values(1)
+                .long("item-id")
+                .short("i")
+                .required_unless("item_name")
+                .required_unless("type")
+                .required_unless("all"),
+        ).arg(
+            Arg::with_name("item_name")
+                .help("Item name to search for in lieu of specifying an item ID")
+                .long("item-name")
+                .short("n")
+                .takes_value(true)
+                .number_of_values(1)
+                .multiple(true)
+                .required_unless("item_id")
+                .required_unless("type")
+                .required_unless("all"),
+        ).arg(
+            Arg::with_name("all")
+                .help("Find pricing data for all items")
+                .long("all")
+                .short("a")
+                .conflicts_with_all(&["item_id", "item_name", "type"]),
+        ).args(&::item_type_args())
+        .arg(
+            Arg::with_name("jobs")
+                .help("Number of items to fetch listings for concurrently")
+                .default_value("1")
+                .long("--jobs")
+                .short("j")
+                .takes_value(true),
+        ).arg(
+            Arg::with_name("resume")
+                .help(
+                    "Resume a previous run using the checkpoint in the output directory, \
+                     skipping items that have already been written",
+                ).long("--resume"),
+        ).arg(
+            Arg::with_name("incremental")
+                .help(
+                    "Only fetch listings newer than those already in the output directory, and \
+                     add them to the existing listings",
+                ).long("--incremental")
+                .conflicts_with("dry_run"),
+        ).args(&::window::args())
+        .arg(
+            Arg::with_name("type_names")
+                .help(
+                    "Fetch the item types, log the type of each item as it is fetched and add \
+                     the type names to the manifest",
+                ).long("--type-names"),
+        ).arg(
+            Arg::with_name("dry_run")
+                .help(
+                    "Print the items that would be fetched, without fetching their listings or \
+                     writing to the output directory",
+                ).long("--dry-run"),
+        ).arg(
+            Arg::with_name("format")
+                .help(
+                    "Format to write listings in. `csv` writes a file per item, while the other \
+                     formats write every item to a single file.",
+                ).default_value("csv")
+                .possible_values(&Format::possible_values())
+                .long("--format")
+                .takes_value(true),
+        ).arg(
+            Arg::with_name("filename_template")
+                .help(
+                    "Name of the file to write the listings of each item to, with `--format csv`. \
+                     `{id}` is replaced with the item ID, and `{name}` with its name in lowercase, \
+                     with anything other than letters and digits replaced with `-`.",
+                ).default_value(FilenameTemplate::DEFAULT)
+                .long("--filename-template")
+                .takes_value(true)
+                .validator(|template| FilenameTemplate::parse(&template).map(|_| ())),
+        ).arg(
+            Arg::with_name("manifest")
+                .help(
+                    "Format of the manifest of every item written, with its metadata, the file \
+                     its listings were written to and the number of listings",
+                ).default_value("csv")
+                .possible_values(&["csv", "json"])
+                .long("--manifest")
+                .takes_value(true),
+        )
+}
+
+/// Resume the paginated iterator from the checkpoint, and record its progress
+///
+/// A page is only recorded once every item from the previous pages has been processed, and only
+/// while no item has failed.
+fn checkpointed<R, T>(
+    iterator: api::PaginatedIterator<R, T>,
+    checkpoint: &Arc<Mutex<Checkpoint>>,
+    idle: &Idle,
+) -> api::PaginatedIterator<R, T> {
+    let url = iterator.url().to_string();
+    let page = checkpoint.lock().expect("not to be poisoned").page(&url);
+    let iterator = match page {
+        Some(page) => {
+            info!("Resuming \"{}\" from page {}", url, page);
+            iterator.starting_at(page)
+        }
+        None => iterator,
+    };
+
+    let checkpoint = Arc::clone(checkpoint);
+    let idle = idle.clone();
+    iterator.on_page(move |page| {
+        idle.wait();
+        let mut checkpoint = checkpoint.lock().expect("not to be poisoned");
+        if let Err(e) = checkpoint.set_page(&url, page) {
+            error!("Unable to update checkpoint: {}", e);
+        }
+    })
+}
+
+/// Item to fetch listings for, with its position in the run
+struct Job {
+    item: data::Item,
+    counter: usize,
+    total: Total,
+}
+
+/// State shared by the workers fetching listings
+struct Worker {
+    api: Arc<api::Api>,
+    output: PathBuf,
+    sink: Box<dyn OutputSink>,
+    /// `None` in a dry run
+    manifest: Option<Manifest>,
+    checkpoint: Arc<Mutex<Checkpoint>>,
+    type_names: Option<data::TypeNames>,
+    /// Only fetch listings newer than those already written
+    incremental: bool,
+    window: Window,
+    failures: Mutex<Vec<String>>,
+}
+
+impl Worker {
+    fn run(&self, job: Job) {
+        let Job {
+            item,
+            counter,
+            total,
+        } = job;
+
+        match self.listing(&item, &total, counter) {
+            Ok(entry) => {
+                if let Some(ref manifest) = self.manifest {
+                    if let Err(e) = manifest.record(entry) {
+                        error!("Unable to update the manifest: {}", e);
+                    }
+                }
+                let mut checkpoint = self.checkpoint.lock().expect("not to be poisoned");
+                if let Err(e) = checkpoint.complete(item.id) {
+                    error!("Unable to update checkpoint: {}", e);
+                }
+            }
+            Err(e) => {
+                let status = e
+                    .downcast_ref::<api::Error>()
+                    .and_then(api::Error::status);
+                if status == Some(reqwest::StatusCode::NOT_FOUND) {
+                    warn!("Skipping item {}: {}", item.name, e);
+                } else {
+                    error!("Error with item {}: {}", item.name, e);
+                }
+                self.checkpoint.lock().expect("not to be poisoned").fail();
+                self.failures
+                    .lock()
+                    .expect("not to be poisoned")
+                    .push(format!("\"{}\" (ID {}): {}", item.name, item.id, e));
+            }
+        };
+    }
+
+    fn listing(
+        &self,
+        item: &data::Item,
+        total: &Total,
+        counter: usize,
+    ) -> Result<manifest::Entry, failure::Error> {
+        let item_type = self
+            .type_names
+            .as_ref()
+            .map(|type_names| format!(" ({})", type_names.describe(item)))
+            .unwrap_or_default();
+        info!(
+            "[{} of {}] Fetching item listings for \"{}\"{}",
+            counter, total, item.name, item_type
+        );
+        let written = if self.incremental {
+            self.sink.written(item)?
+        } else {
+            Default::default()
+        };
+        let buy = self.fetch(item, api::ListingType::Buy, &written)?;
+        let sell = self.fetch(item, api::ListingType::Sell, &written)?;
+
+        let buy_output = buy
+            .iter()
+            .map(|listing| ListingOutput::from_listing(listing, api::ListingType::Buy))
+            .rev();
+
+        let sell_output = sell
+            .iter()
+            .map(|listing| ListingOutput::from_listing(listing, api::ListingType::Sell))
+            .rev();
+
+        let listings_output: Vec<ListingOutput> = buy_output
+            .merge_by(sell_output, |left, right| left.timestamp <= right.timestamp)
+            .collect();
+
+        let path = self.sink.path(item);
+        info!(
+            "[{} of {}] Writing item listings for \"{}\" to \"{}\"",
+            counter,
+            total,
+            item.name,
+            path.to_str().unwrap_or("unknown")
+        );
+        if self.incremental {
+            info!(
+                "[{} of {}] Adding {} new listings for \"{}\"",
+                counter,
+                total,
+                listings_output.len(),
+                item.name
+            );
+            self.sink.append(item, &listings_output)?;
+        } else {
+            self.sink.write(item, &listings_output)?;
+        }
+
+        let path = path.strip_prefix(&self.output).unwrap_or(&path);
+        let entry = manifest::Entry::new(
+            item,
+            path.to_string_lossy().into_owned(),
+            written.buy.count + buy.len(),
+            written.sell.count + sell.len(),
+        );
+        Ok(match self.type_names {
+            Some(ref type_names) => entry.with_type_names(type_names),
+            None => entry,
+        })
+    }
+
+    /// Fetch the listings of the item within the window, newest first. Pages stop being fetched
+    /// once a listing before the window, or in incremental mode one that has already been
+    /// written, is reached.
+    fn fetch(
+        &self,
+        item: &data::Item,
+        listing_type: api::ListingType,
+        written: &Written,
+    ) -> Result<Vec<data::ItemListing>, api::Error> {
+        if !self.incremental && !self.window.is_bounded() {
+            return self.api.listings(item.id, listing_type);
+        }
+
+        let latest = written.get(listing_type).latest;
+        let window = self.window;
+        let listings: Vec<data::ItemListing> = self
+            .api
+            .listings_lazy(item.id, listing_type)
+            .until(move |listing| {
+                window.is_before(listing.timestamp) || Some(listing.timestamp) <= latest
+            }).collect::<Result<_, _>>()?;
+
+        Ok(listings
+            .into_iter()
+            .filter(|listing| window.contains(listing.timestamp))
+            .collect())
+    }
+}
+
+/// Submit a job for every item that has not been completed
+fn listings<I, F>(
+    checkpoint: &Mutex<Checkpoint>,
+    items: I,
+    mut submit: F,
+) -> Result<(), failure::Error>
+where
+    I: Iterator<Item = Result<data::Item, api::Error>>,
+    F: FnMut(Job),
+{
+    let mut counter: usize = 1;
+
+    let mut items = items.peekable();
+    let (_, hint) = items.size_hint();
+    let total: Total = From::from(hint);
+
+    while items.peek().is_some() {
+        let item = items.next().expect("to be some");
+        match item {
+            Ok(item) => {
+                if checkpoint
+                    .lock()
+                    .expect("not to be poisoned")
+                    .is_completed(item.id)
+                {
+                    info!(
+                        "[{} of {}] Skipping completed item \"{}\"",
+                        counter, total, item.name
+                    );
+                } else {
+                    submit(Job {
+                        item,
+                        counter,
+                        total,
+                    });
+                }
+                counter += 1;
+            }
+            Err(e) => {
+                // The paginated iterator would otherwise keep requesting the same page of items
+                if e.page().is_some() {
+                    return Err(e.into());
+                }
+                error!("{}", e);
+            }
+        }
+    }
+
+    Ok(())
+}
+
+pub fn run(api: &Arc<api::Api>, args: &ArgMatches) -> Result<(), failure::Error> {
+    let output = ::output_dir(args)?;
+    let dry_run = args.is_present("dry_run");
+    let resume = args.is_present("resume");
+    let incremental = args.is_present("incremental");
+    let window = Window::from_args(args)?;
+    let checkpoint = if dry_run {
+        Checkpoint::read_only(&output, resume)?
+    } else if resume {
+        Checkpoint::resume(&output)?
+    } else {
+        Checkpoint::create(&output)?
+    };
+    debug!(
+        "Recording progress to \"{}\"",
+        checkpoint.path().to_str().unwrap_or("unknown")
+    );
+    let checkpoint = Arc::new(Mutex::new(checkpoint));
+
+    let type_names = if args.is_present("type_names") {
+        match api.type_names() {
+            Ok(type_names) => Some(type_names),
+            Err(e) => {
+                warn!("Unable to fetch item types; continuing without them: {}", e);
+                None
+            }
+        }
+    } else {
+        None
+    };
+
+    let item_type = ::item_type(api, args)?;
+
+    // Nothing is written in a dry run, and CSV files are only created as items are written
+    let format = if dry_run {
+        Format::Csv
+    } else {
+        Format::from_arg(args.value_of("format").expect("Value to be present"))
+    };
+    let template = args
+        .value_of("filename_template")
+        .expect("Value to be present");
+    let template = FilenameTemplate::parse(template).expect("to be validated");
+    if format == Format::Csv && !template.is_unique() {
+        warn!(
+            "The filename template has no {{id}}, so items with the same name will overwrite \
+             each other"
+        );
+    }
+    let manifest = if dry_run {
+        None
+    } else {
+        let json = args.value_of("manifest") == Some("json");
+        let manifest = Manifest::create(&output, json, resume || incremental)?;
+        debug!(
+            "Recording written items to \"{}\"",
+            manifest.path().to_str().unwrap_or("unknown")
+        );
+        Some(manifest)
+    };
+    let worker = Arc::new(Worker {
+        api: Arc::clone(api),
+        output: output.clone(),
+        sink: format.open(&output, resume || incremental, template)?,
+        manifest,
+        checkpoint: Arc::clone(&checkpoint),
+        type_names,
+        incremental,
+        window,
+        failures: Default::default(),
+    });
+    let pool = {
+        let worker = Arc::clone(&worker);
+        let jobs = value_t!(args, "jobs", usize).unwrap_or_else(|e| e.exit());
+        WorkerPool::new(jobs, move |job| worker.run(job))
+    };
+    let idle = pool.idle();
+
+    let mut dry_run_items = vec![];
+    let submit = |job: Job| {
+        if dry_run {
+            dry_run_items.push(job.item);
+        } else {
+            pool.submit(job);
+        }
+    };
+
+    let result = if args.occurrences_of("all") > 0 {
+        info!("Retrieving data for ALL items");
+        let items = checkpointed(api.items_lazy(), &checkpoint, &idle);
+        listings(&checkpoint, items, submit)
+    } else {
+        let args_item_ids: Vec<u64> = match args.values_of("item_id") {
+            Some(ids) => {
+                let ids: Result<Vec<u64>, _> = ids.map(FromStr::from_str).collect();
+                ids?
+            }
+            None => vec![],
+        };
+
+        let items = args_item_ids
+            .into_iter()
+            .filter(|id| {
+                let completed = checkpoint
+                    .lock()
+                    .expect("not to be poisoned")
+                    .is_completed(*id);
+                if completed {
+                    info!("Skipping completed item ID {}", id);
+                }
+                !completed
+            }).map(|id| api.item(id));
+
+        let item_names: Vec<&str> = match args.values_of("item_name") {
+            Some(items) => items.collect(),
+            None => vec![],
+        };
+
+        let item_searches = item_names.into_iter().map(|item| {
+            info!("Including items from search term \"{}\"", item);
+            checkpointed(api.item_search_lazy(item), &checkpoint, &idle)
+        });
+
+        let type_items = item_type.into_iter().flat_map(|(type_id, sub_type_id)| {
+            info!("Including items of type {}", type_id);
+            checkpointed(api.items_by_type_lazy(type_id), &checkpoint, &idle).filter(move |item| {
+                match (item, sub_type_id) {
+                    (Ok(item), Some(sub_type_id)) => item.sub_type_id == sub_type_id,
+                    _ => true,
+                }
+            })
+        });
+
+        let items = Iterator::flatten(item_searches)
+            .chain(type_items)
+            .chain(items)
+            .unique_by(|item| match item {
+                Ok(item) => Ok(item.id),
+                Err(e) => Err(e.to_string()),
+            });
+        listings(&checkpoint, items, submit)
+    };
+
+    pool.join();
+    let finished = match worker.manifest {
+        Some(ref manifest) => worker.sink.finish().and(manifest.finish()),
+        None => worker.sink.finish(),
+    };
+
+    if dry_run {
+        info!("Would fetch listings for {} items", dry_run_items.len());
+        ::lookup::print_table(&dry_run_items)?;
+    }
+
+    let failures = worker.failures.lock().expect("not to be poisoned");
+    if !failures.is_empty() {
+        warn!("Unable to fetch listings for {} items:", failures.len());
+        for failure in failures.iter() {
+            warn!("\t{}", failure);
+        }
+    }
+
+    result.and(finished)
+}
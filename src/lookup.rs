@@ -0,0 +1,204 @@
+//! Subcommands that print what the API returns, as CSV to stdout
+use clap::{App, Arg, ArgMatches, SubCommand};
+use csv;
+use failure;
+use serde_json;
+use spidy_scrapey::{api, data};
+use std::io::{self, Write};
+
+pub fn items_subcommand<'a, 'b>() -> App<'a, 'b>
+where
+    'a: 'b,
+{
+    SubCommand::with_name("items")
+        .about("Print every item, or every item of a type")
+        .args(&::item_type_args())
+}
+
+pub fn search_subcommand<'a, 'b>() -> App<'a, 'b>
+where
+    'a: 'b,
+{
+    SubCommand::with_name("search")
+        .about("Print the items matching a search term, without fetching their listings")
+        .arg(
+            Arg::with_name("term")
+                .help("Item name to search for")
+                .required(true),
+        ).arg(
+            Arg::with_name("format")
+                .help("Format to print the matching items in")
+                .default_value("table")
+                .possible_values(&["table", "json", "csv"])
+                .long("--format")
+                .takes_value(true),
+        )
+}
+
+pub fn item_subcommand<'a, 'b>() -> App<'a, 'b>
+where
+    'a: 'b,
+{
+    SubCommand::with_name("item")
+        .about("Print items by ID")
+        .arg(
+            Arg::with_name("item_id")
+                .help("Item ID to print")
+                .required(true)
+                .multiple(true),
+        )
+}
+
+pub fn types_subcommand<'a, 'b>() -> App<'a, 'b>
+where
+    'a: 'b,
+{
+    SubCommand::with_name("types").about("Print the item types and their sub-types")
+}
+
+fn print_items<I>(items: I) -> Result<(), failure::Error>
+where
+    I: IntoIterator<Item = Result<data::Item, api::Error>>,
+{
+    let mut wtr = csv::Writer::from_writer(io::stdout());
+    for item in items {
+        wtr.serialize(item?)?;
+    }
+    wtr.flush()?;
+    Ok(())
+}
+
+pub fn items(api: &api::Api, args: &ArgMatches) -> Result<(), failure::Error> {
+    match ::item_type(api, args)? {
+        Some((type_id, sub_type_id)) => print_items(api.items_by_type_lazy(type_id).filter(
+            |item| match (item, sub_type_id) {
+                (Ok(item), Some(sub_type_id)) => item.sub_type_id == sub_type_id,
+                _ => true,
+            },
+        )),
+        None => print_items(api.items_lazy()),
+    }
+}
+
+/// Summary of an item, as printed by `search`
+#[derive(Serialize, Debug)]
+struct ItemSummary<'a> {
+    id: u64,
+    name: &'a str,
+    rarity: String,
+    level: u32,
+    max_offer_unit_price: u64,
+    min_sale_unit_price: u64,
+}
+
+impl<'a> ItemSummary<'a> {
+    fn from_item(item: &'a data::Item) -> Self {
+        Self {
+            id: item.id,
+            name: &item.name,
+            rarity: item.rarity.to_string(),
+            level: item.restriction_level,
+            max_offer_unit_price: item.max_offer_unit_price,
+            min_sale_unit_price: item.min_sale_unit_price,
+        }
+    }
+}
+
+/// Print the items as a table with aligned columns
+pub fn print_table(items: &[data::Item]) -> Result<(), failure::Error> {
+    let header = ["ID", "NAME", "RARITY", "LEVEL", "MAX OFFER", "MIN SALE"];
+    let rows: Vec<Vec<String>> = items
+        .iter()
+        .map(|item| {
+            vec![
+                item.id.to_string(),
+                item.name.clone(),
+                item.rarity.to_string(),
+                item.restriction_level.to_string(),
+                item.max_offer_unit_price.to_string(),
+                item.min_sale_unit_price.to_string(),
+            ]
+        }).collect();
+
+    let mut widths: Vec<usize> = header.iter().map(|column| column.len()).collect();
+    for row in &rows {
+        for (width, column) in widths.iter_mut().zip(row) {
+            *width = (*width).max(column.chars().count());
+        }
+    }
+
+    let stdout = io::stdout();
+    let mut stdout = stdout.lock();
+    let header = header.iter().map(|column| column.to_string()).collect();
+    for row in Some(header).into_iter().chain(rows) {
+        let line: Vec<String> = row
+            .iter()
+            .zip(&widths)
+            .map(|(column, width)| format!("{:1$}", column, width))
+            .collect();
+        writeln!(stdout, "{}", line.join("  ").trim_end())?;
+    }
+    Ok(())
+}
+
+pub fn search(api: &api::Api, args: &ArgMatches) -> Result<(), failure::Error> {
+    let term = args.value_of("term").expect("Value to be present");
+    let items: Result<Vec<data::Item>, api::Error> = api.item_search_lazy(term).collect();
+    let items = items?;
+    info!("Found {} items matching \"{}\"", items.len(), term);
+
+    let summaries = items.iter().map(ItemSummary::from_item);
+    match args.value_of("format").expect("Value to be present") {
+        "json" => {
+            let summaries: Vec<ItemSummary> = summaries.collect();
+            serde_json::to_writer_pretty(io::stdout(), &summaries)?;
+            println!();
+        }
+        "csv" => {
+            let mut wtr = csv::Writer::from_writer(io::stdout());
+            for summary in summaries {
+                wtr.serialize(summary)?;
+            }
+            wtr.flush()?;
+        }
+        _ => print_table(&items)?,
+    }
+    Ok(())
+}
+
+pub fn item(api: &api::Api, args: &ArgMatches) -> Result<(), failure::Error> {
+    let ids = values_t!(args, "item_id", u64).unwrap_or_else(|e| e.exit());
+    print_items(ids.into_iter().map(|id| api.item(id)))
+}
+
+#[derive(Serialize, Debug)]
+struct TypeOutput<'a> {
+    type_id: u64,
+    type_name: &'a str,
+    sub_type_id: Option<u64>,
+    sub_type_name: Option<&'a str>,
+}
+
+pub fn types(api: &api::Api, _args: &ArgMatches) -> Result<(), failure::Error> {
+    let types = api.types()?;
+    let mut wtr = csv::Writer::from_writer(io::stdout());
+
+    for item_type in &types {
+        wtr.serialize(TypeOutput {
+            type_id: item_type.id,
+            type_name: &item_type.name,
+            sub_type_id: None,
+            sub_type_name: None,
+        })?;
+        for sub_type in &item_type.sub_types {
+            wtr.serialize(TypeOutput {
+                type_id: item_type.id,
+                type_name: &item_type.name,
+                sub_type_id: Some(sub_type.id),
+                sub_type_name: Some(&sub_type.name),
+            })?;
+        }
+    }
+    wtr.flush()?;
+    Ok(())
+}
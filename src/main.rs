@@ -5,86 +5,39 @@ extern crate log;
 #[macro_use]
 extern crate serde_derive;
 
-extern crate backoff;
 extern crate chrono;
 extern crate csv;
 extern crate failure;
 extern crate itertools;
+#[cfg(feature = "parquet")]
+extern crate parquet;
 extern crate reqwest;
+#[cfg(feature = "sqlite")]
+extern crate rusqlite;
 extern crate serde;
 extern crate serde_json;
+extern crate spidy_scrapey;
 extern crate stderrlog;
 
-#[macro_use]
-mod custom_serde;
-mod api;
-mod data;
+mod aggregate;
+mod checkpoint;
+mod gems;
+mod listings;
+mod lookup;
+mod manifest;
+mod output;
+mod profit;
+mod recipes;
+mod spread;
+mod window;
+mod workers;
 
-use chrono::{DateTime, Utc};
 use clap::{App, AppSettings, Arg, ArgMatches};
-use itertools::Itertools;
-use std::fmt;
 use std::fs;
-use std::ops::Deref;
-use std::path::Path;
-use std::str::FromStr;
-
-// Output Listing
-#[derive(Serialize, Debug)]
-struct ListingOutput<'a> {
-    #[serde(with = "::custom_serde::timestamp")]
-    pub timestamp: &'a DateTime<Utc>,
-
-    #[serde(rename = "type")]
-    pub listing_type: api::ListingType,
-    pub unit_price: u64,
-    pub quantity: u64,
-    pub listings: u64,
-}
-
-impl<'a> ListingOutput<'a> {
-    pub fn from_listing(listing: &'a data::ItemListing, listing_type: api::ListingType) -> Self {
-        Self {
-            timestamp: &listing.timestamp,
-            listing_type,
-            unit_price: listing.unit_price,
-            quantity: listing.quantity,
-            listings: listing.listings,
-        }
-    }
-}
-
-/// "Total" count
-pub struct Total(Option<usize>);
-
-impl Deref for Total {
-    type Target = Option<usize>;
-
-    fn deref(&self) -> &Self::Target {
-        &self.0
-    }
-}
-
-impl Default for Total {
-    fn default() -> Self {
-        Total(None)
-    }
-}
-
-impl fmt::Display for Total {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        match self.0 {
-            None => write!(f, "unknown"),
-            Some(count) => write!(f, "{}", count),
-        }
-    }
-}
-
-impl From<Option<usize>> for Total {
-    fn from(count: Option<usize>) -> Self {
-        Total(count)
-    }
-}
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use spidy_scrapey::{api, pacing};
+use std::time::Duration;
 
 fn make_parser<'a, 'b>() -> App<'a, 'b>
 where
@@ -95,45 +48,21 @@ where
         .author(crate_authors!())
         .global_setting(AppSettings::DontCollapseArgsInUsage)
         .global_setting(AppSettings::NextLineHelp)
-        .about(
-            "Fetch price listing data from GW2Spidy. \
-             Specify items by IDs or their names.",
-        ).arg(
+        .setting(AppSettings::SubcommandRequiredElseHelp)
+        .about("Fetch price listing and other data from GW2Spidy.")
+        .arg(
             Arg::with_name("verbosity")
                 .short("v")
                 .multiple(true)
+                .global(true)
                 .help("Increase message verbosity"),
-        ).arg(
-            Arg::with_name("item_id")
-                .help("Item ID to fetch pricing data for")
-                .required(true)
-                .multiple(true)
-                .takes_value(true)
-                .number_of_values(1)
-                .long("item-id")
-                .short("i")
-                .required_unless("item_name")
-                .required_unless("all"),
-        ).arg(
-            Arg::with_name("item_name")
-                .help("Item name to search for in lieu of specifying an item ID")
-                .long("item-name")
-                .short("n")
-                .takes_value(true)
-                .number_of_values(1)
-                .multiple(true)
-                .required_unless("item_id")
-                .required_unless("all"),
-        ).arg(
-            Arg::with_name("all")
-                .help("Find pricing data for all items")
-                .long("all")
-                .short("a")
-                .conflicts_with_all(&["item_id", "item_name"]),
         ).arg(
             Arg::with_name("output")
                 .help("Path to directory to output CSV files to")
                 .default_value("output")
+                .long("--output")
+                .short("o")
+                .global(true)
                 .takes_value(true),
         ).arg(
             Arg::with_name("max_backoff")
@@ -141,137 +70,254 @@ where
                     "Max duration, in seconds, for the exponential Backoff delay between API calls",
                 ).default_value("1")
                 .long("--max-backoff")
+                .global(true)
+                .takes_value(true),
+        ).arg(
+            Arg::with_name("pacing")
+                .help(
+                    "Strategy for pacing requests to paginated APIs. \
+                     `exponential` uses `--max-backoff`, `fixed` uses `--pacing-rate`, and \
+                     `token-bucket` uses `--pacing-rate` and `--pacing-burst`.",
+                ).default_value("exponential")
+                .possible_values(&["exponential", "fixed", "token-bucket"])
+                .long("--pacing")
+                .global(true)
+                .takes_value(true),
+        ).arg(
+            Arg::with_name("pacing_rate")
+                .help("Requests per second for the `fixed` and `token-bucket` pacing strategies")
+                .default_value("2")
+                .long("--pacing-rate")
+                .global(true)
+                .takes_value(true)
+                .validator(|rate| match rate.parse::<f64>() {
+                    Ok(rate) if rate > 0.0 => Ok(()),
+                    _ => Err("must be a positive number".to_string()),
+                }),
+        ).arg(
+            Arg::with_name("pacing_burst")
+                .help("Max burst of requests for the `token-bucket` pacing strategy")
+                .default_value("5")
+                .long("--pacing-burst")
+                .global(true)
+                .takes_value(true),
+        ).arg(
+            Arg::with_name("rate_limit")
+                .help(
+                    "Limit all API calls to RATE requests per second, with bursts of up to \
+                     BURST requests. Specified as RATE or RATE/BURST; BURST defaults to 1.",
+                ).long("--rate-limit")
+                .value_name("RATE[/BURST]")
+                .global(true)
+                .takes_value(true)
+                .validator(|limit| parse_rate_limit(&limit).map(|_| ())),
+        ).arg(
+            Arg::with_name("max_attempts")
+                .help(
+                    "Max number of attempts for each API call that fails with a connection error, \
+                     HTTP 429 or HTTP 5xx. Set to 1 to disable retries.",
+                ).default_value("5")
+                .long("--max-attempts")
+                .global(true)
+                .takes_value(true),
+        ).arg(
+            Arg::with_name("max_retry_time")
+                .help("Max duration, in seconds, to spend retrying each API call")
+                .default_value("300")
+                .long("--max-retry-time")
+                .global(true)
+                .takes_value(true),
+        ).arg(
+            Arg::with_name("base_url")
+                .help("Base URL of the GW2Spidy API, without the API version")
+                .default_value(api::DEFAULT_BASE_URL)
+                .long("--base-url")
+                .global(true)
+                .takes_value(true),
+        ).arg(
+            Arg::with_name("api_format")
+                .help("Format to request API responses in")
+                .default_value("json")
+                .possible_values(&["json", "csv"])
+                .long("--api-format")
+                .global(true)
                 .takes_value(true),
-        )
+        ).arg(
+            Arg::with_name("timeout")
+                .help("Timeout, in seconds, for each API request")
+                .default_value("30")
+                .long("--timeout")
+                .global(true)
+                .takes_value(true),
+        ).subcommand(listings::subcommand())
+        .subcommand(lookup::items_subcommand())
+        .subcommand(lookup::search_subcommand())
+        .subcommand(lookup::item_subcommand())
+        .subcommand(lookup::types_subcommand())
+        .subcommand(recipes::subcommand())
+        .subcommand(profit::subcommand())
+        .subcommand(gems::subcommand())
+        .subcommand(aggregate::subcommand())
+        .subcommand(spread::subcommand())
 }
 
-fn listings<'a, I>(api: &api::Api, args: &ArgMatches<'a>, items: I) -> Result<(), failure::Error>
-where
-    I: Iterator<Item = Result<data::Item, reqwest::Error>>,
-{
-    let output = args.value_of("output").expect("Value to be present");
-    let output = Path::new(output);
+/// `--type` and `--sub-type` arguments, resolved with `item_type`
+fn item_type_args<'a, 'b>() -> Vec<Arg<'a, 'b>> {
+    vec![
+        Arg::with_name("type")
+            .help("Item type, by ID or name, to include all items of")
+            .long("--type")
+            .takes_value(true),
+        Arg::with_name("sub_type")
+            .help("Only include items of this sub-type of `--type`, by ID or name")
+            .long("--sub-type")
+            .takes_value(true)
+            .requires("type"),
+    ]
+}
 
-    let output = if output.is_relative() {
-        std::env::current_dir()?.join(output)
-    } else {
-        output.to_path_buf()
+fn pacing(args: &ArgMatches) -> pacing::Pacing {
+    let rate = value_t!(args, "pacing_rate", f64).unwrap_or_else(|e| e.exit());
+
+    match args.value_of("pacing").expect("Value to be present") {
+        "fixed" => pacing::Pacing::FixedRate {
+            interval: Duration::from_millis((1000.0 / rate) as u64),
+        },
+        "token-bucket" => pacing::Pacing::TokenBucket {
+            rate,
+            burst: value_t!(args, "pacing_burst", u32).unwrap_or_else(|e| e.exit()),
+        },
+        _ => pacing::Pacing::Exponential {
+            max_interval: Duration::from_secs(
+                value_t!(args, "max_backoff", u64).unwrap_or_else(|e| e.exit()),
+            ),
+        },
+    }
+}
+
+/// Resolve `--type` and `--sub-type`, which are either IDs or names, to IDs
+fn item_type(
+    api: &api::Api,
+    args: &ArgMatches,
+) -> Result<Option<(u64, Option<u64>)>, failure::Error> {
+    let item_type = match args.value_of("type") {
+        Some(item_type) => item_type,
+        None => return Ok(None),
     };
+    let sub_type = args.value_of("sub_type");
 
-    fs::create_dir_all(&output)?;
+    // Only fetch the types if there are names to resolve
+    let is_id = |value: &str| value.parse::<u64>().is_ok();
+    let type_names = if is_id(item_type) && sub_type.into_iter().all(is_id) {
+        Default::default()
+    } else {
+        api.type_names()?
+    };
 
-    let mut counter: usize = 1;
+    let type_id = match item_type.parse() {
+        Ok(id) => id,
+        Err(_) => type_names.find_type(item_type).ok_or_else(|| {
+            failure::err_msg(format!("Unknown item type \"{}\"", item_type))
+        })?,
+    };
+    let sub_type_id = match sub_type {
+        Some(sub_type) => Some(match sub_type.parse() {
+            Ok(id) => id,
+            Err(_) => type_names.find_sub_type(type_id, sub_type).ok_or_else(|| {
+                failure::err_msg(format!(
+                    "Unknown sub-type \"{}\" of item type {}",
+                    sub_type, type_id
+                ))
+            })?,
+        }),
+        None => None,
+    };
 
-    let mut items = items.peekable();
-    let (_, hint) = items.size_hint();
-    let total: Total = From::from(hint);
+    Ok(Some((type_id, sub_type_id)))
+}
 
-    while items.peek().is_some() {
-        let item = items.next().expect("to be some");
-        match item {
-            Ok(item) => {
-                match listing(api, &item, &total, counter, &output) {
-                    Ok(()) => {
-                        counter = counter + 1;
-                    }
-                    Err(e) => {
-                        error!("Error with item {}: {}", item.name, e);
-                    }
-                };
-            }
-            Err(e) => error!("{}", e),
-        }
+fn api_format(args: &ArgMatches) -> api::ApiFormat {
+    match args.value_of("api_format").expect("Value to be present") {
+        "csv" => api::ApiFormat::Csv,
+        _ => api::ApiFormat::Json,
     }
-
-    Ok(())
 }
 
-fn listing(
-    api: &api::Api,
-    item: &data::Item,
-    total: &Total,
-    counter: usize,
-    output: &Path,
-) -> Result<(), failure::Error> {
-    info!(
-        "[{} of {}] Fetching item listings for \"{}\"",
-        counter, total, item.name
-    );
-    let buy = api.listings(item.id, api::ListingType::Buy)?;
-    let sell = api.listings(item.id, api::ListingType::Sell)?;
-
-    let buy_output = buy
-        .iter()
-        .map(|listing| ListingOutput::from_listing(listing, api::ListingType::Buy))
-        .rev();
-
-    let sell_output = sell
-        .iter()
-        .map(|listing| ListingOutput::from_listing(listing, api::ListingType::Sell))
-        .rev();
-
-    let listings_output =
-        buy_output.merge_by(sell_output, |left, right| left.timestamp <= right.timestamp);
+fn parse_rate_limit(limit: &str) -> Result<(f64, u32), String> {
+    let mut parts = limit.splitn(2, '/');
+    let rate = match parts.next().map(str::parse::<f64>) {
+        Some(Ok(rate)) if rate > 0.0 => rate,
+        _ => return Err("rate must be a positive number".to_string()),
+    };
+    let burst = match parts.next().map(str::parse::<u32>) {
+        None => 1,
+        Some(Ok(burst)) if burst > 0 => burst,
+        _ => return Err("burst must be a positive integer".to_string()),
+    };
+    Ok((rate, burst))
+}
 
-    let path = output.join(format!("{}.csv", item.name));
-    info!(
-        "[{} of {}] Writing item listings for \"{}\" to \"{}\"",
-        counter,
-        total,
-        item.name,
-        path.to_str().unwrap_or_else(|| "unknown")
-    );
-    let mut wtr = csv::Writer::from_path(&path)?;
+fn output_dir(args: &ArgMatches) -> Result<PathBuf, failure::Error> {
+    let output = args.value_of("output").expect("Value to be present");
+    let output = Path::new(output);
 
-    for listing in listings_output {
-        wtr.serialize(listing)?;
-    }
+    let output = if output.is_relative() {
+        std::env::current_dir()?.join(output)
+    } else {
+        output.to_path_buf()
+    };
 
-    Ok(())
+    fs::create_dir_all(&output)?;
+    Ok(output)
 }
 
 fn main() -> Result<(), failure::Error> {
     let args = make_parser().get_matches();
+    // Global options are available from the subcommand whether they come before or after it
+    let (subcommand, args) = match args.subcommand() {
+        (name, Some(args)) => (name, args),
+        _ => unreachable!("a subcommand to be required"),
+    };
     let verbose = args.occurrences_of("verbosity") as usize;
     let verbose = if verbose == 0 { 2 } else { verbose };
 
     stderrlog::new().verbosity(verbose).init()?;
 
-    let api = api::Api::new(
-        api::ApiFormat::Json,
-        value_t!(args, "max_backoff", u64).unwrap_or_else(|e| e.exit()),
-    );
-
-    if args.occurrences_of("all") > 0 {
-        info!("Retrieving data for ALL items");
-        listings(&api, &args, api.items_lazy())
-    } else {
-        let args_item_ids: Vec<u64> = match args.values_of("item_id") {
-            Some(ids) => {
-                let ids: Result<Vec<u64>, _> = ids.map(FromStr::from_str).collect();
-                ids?
-            }
-            None => vec![],
-        };
-
-        let items = args_item_ids.into_iter().map(|id| api.item(id));
-
-        let item_names: Vec<&str> = match args.values_of("item_name") {
-            Some(items) => items.collect(),
-            None => vec![],
-        };
-
-        let item_searches = item_names.into_iter().map(|item| {
-            info!("Including items from search term \"{}\"", item);
-            api.item_search_lazy(item)
-        });
-
-        let items = Iterator::flatten(item_searches)
-            .chain(items)
-            .unique_by(|item| match item {
-                Ok(v) => v.name.to_string(),
-                Err(e) => format!("{}", e),
-            });
-        listings(&api, &args, items)
+    let mut builder = api::Api::builder()
+        .base_url(args.value_of("base_url").expect("Value to be present"))
+        .format(api_format(args))
+        .pacing(pacing(args))
+        .retry(api::Retry {
+            max_attempts: value_t!(args, "max_attempts", u32).unwrap_or_else(|e| e.exit()),
+            max_elapsed_time: Duration::from_secs(
+                value_t!(args, "max_retry_time", u64).unwrap_or_else(|e| e.exit()),
+            ),
+            ..Default::default()
+        })
+        .timeout(Duration::from_secs(
+            value_t!(args, "timeout", u64).unwrap_or_else(|e| e.exit()),
+        )).user_agent(concat!(crate_name!(), "/", crate_version!()));
+    if let Some(limit) = args.value_of("rate_limit") {
+        let (rate, burst) = parse_rate_limit(limit).expect("to be validated");
+        builder = builder.rate_limit(rate, burst);
+    }
+    let api = Arc::new(builder.build()?);
+
+    let (result, failure) = match subcommand {
+        "listings" => (listings::run(&api, args), "fetch listings"),
+        "items" => (lookup::items(&api, args), "fetch items"),
+        "search" => (lookup::search(&api, args), "search items"),
+        "item" => (lookup::item(&api, args), "fetch items"),
+        "types" => (lookup::types(&api, args), "fetch item types"),
+        "recipes" => (recipes::run(&api, args), "dump recipes"),
+        "profit" => (profit::run(&api, args), "calculate profits"),
+        "gems" => (gems::run(&api, args), "fetch gem prices"),
+        "aggregate" => (aggregate::run(args), "aggregate listings"),
+        "spread" => (spread::run(args), "join listings"),
+        _ => unreachable!("every subcommand to be handled"),
+    };
+    if let Err(ref e) = result {
+        error!("Unable to {}: {}", failure, e);
     }
+    result
 }
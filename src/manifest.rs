@@ -0,0 +1,237 @@
+//! Manifest of the items whose listings have been written, so that listings can be joined back
+//! to the item metadata
+use chrono::{DateTime, Utc};
+use csv;
+use failure;
+use serde_json;
+use spidy_scrapey::data;
+use std::collections::HashSet;
+use std::fs::{self, File, OpenOptions};
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Entry {
+    pub id: u64,
+    pub name: String,
+    pub rarity: data::Rarity,
+    pub restriction_level: u32,
+    pub img: String,
+    #[serde(with = "::spidy_scrapey::custom_serde::timestamp")]
+    pub price_last_changed: DateTime<Utc>,
+    pub max_offer_unit_price: u64,
+    pub min_sale_unit_price: u64,
+    pub offer_availability: u64,
+    pub sale_availability: u64,
+    pub sale_price_change_last_hour: i32,
+    pub offer_price_change_last_hour: i32,
+    pub type_id: u64,
+    pub sub_type_id: u64,
+    /// Only resolved with `--type-names`
+    #[serde(default)]
+    pub type_name: Option<String>,
+    #[serde(default)]
+    pub sub_type_name: Option<String>,
+
+    /// Path of the listings, relative to the output directory
+    pub output: String,
+    pub buy_listings: usize,
+    pub sell_listings: usize,
+    #[serde(with = "::spidy_scrapey::custom_serde::timestamp")]
+    pub fetched_at: DateTime<Utc>,
+}
+
+impl Entry {
+    pub fn new(item: &data::Item, output: String, buy: usize, sell: usize) -> Self {
+        Self {
+            id: item.id,
+            name: item.name.clone(),
+            rarity: item.rarity,
+            restriction_level: item.restriction_level,
+            img: item.img.clone(),
+            price_last_changed: item.price_last_changed,
+            max_offer_unit_price: item.max_offer_unit_price,
+            min_sale_unit_price: item.min_sale_unit_price,
+            offer_availability: item.offer_availability,
+            sale_availability: item.sale_availability,
+            sale_price_change_last_hour: item.sale_price_change_last_hour,
+            offer_price_change_last_hour: item.offer_price_change_last_hour,
+            type_id: item.type_id,
+            sub_type_id: item.sub_type_id,
+            type_name: None,
+            sub_type_name: None,
+            output,
+            buy_listings: buy,
+            sell_listings: sell,
+            fetched_at: Utc::now(),
+        }
+    }
+
+    /// Resolve the names of the item's type and sub-type
+    pub fn with_type_names(mut self, type_names: &data::TypeNames) -> Self {
+        self.type_name = type_names.type_name(self.type_id).map(str::to_string);
+        self.sub_type_name = type_names
+            .sub_type_name(self.type_id, self.sub_type_id)
+            .map(str::to_string);
+        self
+    }
+}
+
+enum Writer {
+    /// Rows are appended as items are written
+    Csv(Box<csv::Writer<File>>),
+    /// A JSON array cannot be appended to, so it is written when the manifest is finished.
+    /// Until then, entries are appended to a JSON Lines journal that a resumed run picks up.
+    Json { entries: Vec<Entry>, journal: File },
+}
+
+/// Entries in the journal of an unfinished JSON manifest. Invalid lines are skipped.
+fn read_journal(path: &Path) -> Result<Vec<Entry>, failure::Error> {
+    let mut entries = vec![];
+    if !path.exists() {
+        return Ok(entries);
+    }
+
+    let reader = BufReader::new(File::open(path)?);
+    for (number, line) in reader.lines().enumerate() {
+        match serde_json::from_str(&line?) {
+            Ok(entry) => entries.push(entry),
+            // Most likely a line that was being written when the process died
+            Err(e) => warn!(
+                "Ignoring invalid manifest line {} in \"{}\": {}",
+                number + 1,
+                path.to_str().unwrap_or("unknown"),
+                e
+            ),
+        }
+    }
+    Ok(entries)
+}
+
+/// Manifest in the output directory, in CSV or JSON
+pub struct Manifest {
+    path: PathBuf,
+    writer: Mutex<Writer>,
+}
+
+impl Manifest {
+    pub const CSV_FILENAME: &'static str = "items.csv";
+    pub const JSON_FILENAME: &'static str = "items.json";
+    pub const JOURNAL_FILENAME: &'static str = "items.partial.jsonl";
+
+    /// Start a manifest in the output directory. When `resume` is set, the entries of the
+    /// existing manifest are kept.
+    pub fn create(output: &Path, json: bool, resume: bool) -> Result<Self, failure::Error> {
+        let (path, writer) = if json {
+            let path = output.join(Self::JSON_FILENAME);
+            let journal_path = output.join(Self::JOURNAL_FILENAME);
+            let mut entries = vec![];
+            if resume {
+                if path.exists() {
+                    entries = serde_json::from_reader(File::open(&path)?)?;
+                }
+                entries.extend(read_journal(&journal_path)?);
+            } else if path.exists() {
+                // Otherwise a run that is interrupted and then resumed would pick it up
+                fs::remove_file(&path)?;
+            }
+
+            // Rewritten so that a partly written line is not appended to
+            let mut journal = File::create(&journal_path)?;
+            for entry in &entries {
+                serde_json::to_writer(&mut journal, entry)?;
+                journal.write_all(b"\n")?;
+            }
+            (path, Writer::Json { entries, journal })
+        } else {
+            let path = output.join(Self::CSV_FILENAME);
+            let file = OpenOptions::new()
+                .create(true)
+                .write(true)
+                .append(resume)
+                .truncate(!resume)
+                .open(&path)?;
+            let is_empty = file.metadata()?.len() == 0;
+            let writer = csv::WriterBuilder::new()
+                .has_headers(is_empty)
+                .from_writer(file);
+            (path, Writer::Csv(Box::new(writer)))
+        };
+
+        Ok(Self {
+            path,
+            writer: Mutex::new(writer),
+        })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn record(&self, entry: Entry) -> Result<(), failure::Error> {
+        match *self.writer.lock().expect("not to be poisoned") {
+            Writer::Csv(ref mut writer) => {
+                writer.serialize(entry)?;
+                writer.flush()?;
+            }
+            Writer::Json {
+                ref mut entries,
+                ref mut journal,
+            } => {
+                serde_json::to_writer(&mut *journal, &entry)?;
+                journal.write_all(b"\n")?;
+                entries.push(entry);
+            }
+        }
+        Ok(())
+    }
+
+    /// Write out any entries that have not been written yet, keeping only the latest entry of
+    /// each item
+    pub fn finish(&self) -> Result<(), failure::Error> {
+        match *self.writer.lock().expect("not to be poisoned") {
+            Writer::Csv(ref mut writer) => {
+                writer.flush()?;
+                let mut entries: Vec<Entry> = vec![];
+                for entry in csv::Reader::from_path(&self.path)?.deserialize() {
+                    entries.push(entry?);
+                }
+                let latest = latest(&entries);
+                if latest.len() == entries.len() {
+                    return Ok(());
+                }
+
+                let partial = self.path.with_extension("csv.tmp");
+                let mut partial_writer = csv::Writer::from_path(&partial)?;
+                for entry in latest {
+                    partial_writer.serialize(entry)?;
+                }
+                partial_writer.flush()?;
+                fs::rename(&partial, &self.path)?;
+            }
+            Writer::Json { ref entries, .. } => {
+                // Written in full before replacing the journal, so that an interruption loses
+                // nothing
+                let partial = self.path.with_extension("json.tmp");
+                serde_json::to_writer_pretty(File::create(&partial)?, &latest(entries))?;
+                fs::rename(&partial, &self.path)?;
+                fs::remove_file(self.path.with_file_name(Self::JOURNAL_FILENAME))?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Latest entry of each item, in the order they were recorded. Items are written again on resume
+/// and in incremental mode.
+fn latest(entries: &[Entry]) -> Vec<&Entry> {
+    let mut seen = HashSet::new();
+    let mut latest: Vec<&Entry> = entries
+        .iter()
+        .rev()
+        .filter(|entry| seen.insert(entry.id))
+        .collect();
+    latest.reverse();
+    latest
+}
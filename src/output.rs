@@ -0,0 +1,937 @@
+//! Sinks that the `listings` subcommand writes item listings to
+use chrono::{DateTime, Utc};
+use clap::ArgMatches;
+use csv;
+use failure;
+use serde::Serialize;
+use serde_json;
+use spidy_scrapey::{api, data};
+use std::collections::HashMap;
+use std::fs::{self, File, OpenOptions};
+use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+// Output Listing
+#[derive(Serialize, Debug)]
+pub struct ListingOutput<'a> {
+    #[serde(with = "::spidy_scrapey::custom_serde::timestamp")]
+    pub timestamp: &'a DateTime<Utc>,
+
+    #[serde(rename = "type")]
+    pub listing_type: api::ListingType,
+    pub unit_price: u64,
+    pub quantity: u64,
+    pub listings: u64,
+}
+
+impl<'a> ListingOutput<'a> {
+    pub fn from_listing(listing: &'a data::ItemListing, listing_type: api::ListingType) -> Self {
+        Self {
+            timestamp: &listing.timestamp,
+            listing_type,
+            unit_price: listing.unit_price,
+            quantity: listing.quantity,
+            listings: listing.listings,
+        }
+    }
+}
+
+/// Listing as read back from a sink
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+pub struct StoredListing {
+    /// Only written to the sinks that hold more than one item
+    #[serde(default)]
+    pub item_id: u64,
+    #[serde(with = "::spidy_scrapey::custom_serde::timestamp")]
+    pub timestamp: DateTime<Utc>,
+    #[serde(rename = "type")]
+    pub listing_type: api::ListingType,
+    pub unit_price: u64,
+    pub quantity: u64,
+    pub listings: u64,
+}
+
+/// Read the listings of an item from a CSV file written by `CsvSink`
+pub fn read_csv(path: &Path) -> Result<Vec<StoredListing>, failure::Error> {
+    let mut listings = vec![];
+    for listing in csv::Reader::from_path(path)?.deserialize() {
+        listings.push(listing?);
+    }
+    Ok(listings)
+}
+
+/// Read the listing CSV files in the `file` argument and write the rows that `transform` turns
+/// their listings into to the output directory, as FILE.SUFFIX.csv
+pub fn transform_files<T, F>(
+    args: &ArgMatches,
+    suffix: &str,
+    transform: F,
+) -> Result<(), failure::Error>
+where
+    T: Serialize,
+    F: Fn(&[StoredListing]) -> Vec<T>,
+{
+    let output = ::output_dir(args)?;
+
+    for file in args.values_of("file").expect("Value to be present") {
+        let input = Path::new(file);
+        let stem = input
+            .file_stem()
+            .ok_or_else(|| failure::err_msg(format!("\"{}\" is not a file", file)))?;
+        let path = output.join(format!("{}.{}.csv", stem.to_string_lossy(), suffix));
+
+        let rows = transform(&read_csv(input)?);
+        info!(
+            "Writing {} rows of \"{}\" to \"{}\"",
+            rows.len(),
+            file,
+            path.to_str().unwrap_or("unknown")
+        );
+        let mut wtr = csv::Writer::from_path(&path)?;
+        for row in rows {
+            wtr.serialize(row)?;
+        }
+        wtr.flush()?;
+    }
+
+    Ok(())
+}
+
+/// Listing of item 0 with a single listing, for tests
+#[cfg(test)]
+pub fn stored_listing(
+    timestamp: &str,
+    listing_type: api::ListingType,
+    unit_price: u64,
+    quantity: u64,
+) -> StoredListing {
+    StoredListing {
+        item_id: 0,
+        timestamp: timestamp.parse().expect("a valid timestamp"),
+        listing_type,
+        unit_price,
+        quantity,
+        listings: 1,
+    }
+}
+
+/// Listings of one type that have been written for an item
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct Listed {
+    pub latest: Option<DateTime<Utc>>,
+    pub count: usize,
+}
+
+impl Listed {
+    fn add(&mut self, latest: DateTime<Utc>, count: usize) {
+        if self.latest < Some(latest) {
+            self.latest = Some(latest);
+        }
+        self.count += count;
+    }
+}
+
+/// Listings that have been written for an item
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct Written {
+    pub buy: Listed,
+    pub sell: Listed,
+}
+
+impl Written {
+    pub fn get(&self, listing_type: api::ListingType) -> Listed {
+        match listing_type {
+            api::ListingType::Buy => self.buy,
+            api::ListingType::Sell => self.sell,
+        }
+    }
+
+    fn get_mut(&mut self, listing_type: api::ListingType) -> &mut Listed {
+        match listing_type {
+            api::ListingType::Buy => &mut self.buy,
+            api::ListingType::Sell => &mut self.sell,
+        }
+    }
+
+    fn add(&mut self, listing_type: api::ListingType, timestamp: DateTime<Utc>) {
+        self.get_mut(listing_type).add(timestamp, 1);
+    }
+}
+
+/// Format to write listings in, selected with `--format`
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Format {
+    /// One CSV file per item
+    Csv,
+    /// A single JSON Lines file for every item
+    JsonLines,
+    /// A single Apache Parquet file for every item
+    #[cfg(feature = "parquet")]
+    Parquet,
+    /// A single SQLite database with an `items` and a `listings` table
+    #[cfg(feature = "sqlite")]
+    Sqlite,
+}
+
+impl Format {
+    /// Values accepted by `--format`
+    pub fn possible_values() -> Vec<&'static str> {
+        let mut values = vec!["csv", "jsonl"];
+        if cfg!(feature = "parquet") {
+            values.push("parquet");
+        }
+        if cfg!(feature = "sqlite") {
+            values.push("sqlite");
+        }
+        values
+    }
+
+    pub fn from_arg(format: &str) -> Self {
+        match format {
+            "jsonl" => Format::JsonLines,
+            #[cfg(feature = "parquet")]
+            "parquet" => Format::Parquet,
+            #[cfg(feature = "sqlite")]
+            "sqlite" => Format::Sqlite,
+            _ => Format::Csv,
+        }
+    }
+
+    /// Open a sink in the output directory. When `keep` is set, listings that have already been
+    /// written are kept. The template only applies to formats with a file per item.
+    pub fn open(
+        self,
+        output: &Path,
+        keep: bool,
+        template: FilenameTemplate,
+    ) -> Result<Box<dyn OutputSink>, failure::Error> {
+        Ok(match self {
+            Format::Csv => Box::new(CsvSink::new(output, template)),
+            Format::JsonLines => Box::new(JsonLinesSink::open(output, keep)?),
+            #[cfg(feature = "parquet")]
+            Format::Parquet => Box::new(parquet_sink::ParquetSink::create(output, keep)?),
+            #[cfg(feature = "sqlite")]
+            Format::Sqlite => Box::new(sqlite_sink::SqliteSink::open(output, keep)?),
+        })
+    }
+}
+
+/// Destination of the listings of every item in a run. Sinks are shared between workers.
+pub trait OutputSink: Send + Sync {
+    /// Path that the listings of the item are written to
+    fn path(&self, item: &data::Item) -> PathBuf;
+
+    /// Write the listings of the item, oldest first, replacing any that were written before
+    fn write(&self, item: &data::Item, listings: &[ListingOutput]) -> Result<(), failure::Error>;
+
+    /// Listings of the item that have been written
+    fn written(&self, _item: &data::Item) -> Result<Written, failure::Error> {
+        Ok(Default::default())
+    }
+
+    /// Write listings of the item, oldest first, that are newer than those already written
+    fn append(&self, item: &data::Item, listings: &[ListingOutput]) -> Result<(), failure::Error> {
+        self.write(item, listings)
+    }
+
+    /// Flush everything that has been written. Called once, after the last item.
+    fn finish(&self) -> Result<(), failure::Error> {
+        Ok(())
+    }
+}
+
+/// Lowercase `name`, replacing every run of characters other than letters and digits with `-`
+pub fn slug(name: &str) -> String {
+    let mut slug = String::with_capacity(name.len());
+    for c in name.chars().flat_map(char::to_lowercase) {
+        if c.is_alphanumeric() {
+            slug.push(c);
+        } else if !slug.is_empty() && !slug.ends_with('-') {
+            slug.push('-');
+        }
+    }
+    let slug = slug.trim_end_matches('-');
+
+    if slug.is_empty() {
+        "item".to_string()
+    } else {
+        slug.to_string()
+    }
+}
+
+/// Name of the file that the listings of an item are written to, with `{id}` and `{name}`
+/// placeholders for the item ID and the `slug` of its name
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct FilenameTemplate(String);
+
+impl FilenameTemplate {
+    pub const DEFAULT: &'static str = "{id}-{name}.csv";
+
+    pub fn parse(template: &str) -> Result<Self, String> {
+        if template.contains(&['/', '\\'][..]) {
+            return Err("must be a file name, without directories".to_string());
+        }
+        // Placeholders never render empty, so only these can name something other than a file
+        if let "" | "." | ".." = template {
+            return Err("must be a file name".to_string());
+        }
+
+        let mut rest = template;
+        while let Some(start) = rest.find('{') {
+            let end = match rest[start..].find('}') {
+                Some(end) => start + end,
+                None => return Err("has an unterminated placeholder".to_string()),
+            };
+            match &rest[start + 1..end] {
+                "id" | "name" => {}
+                placeholder => {
+                    return Err(format!("has an unknown placeholder {{{}}}", placeholder))
+                }
+            }
+            rest = &rest[end + 1..];
+        }
+
+        Ok(FilenameTemplate(template.to_string()))
+    }
+
+    /// Whether every item gets a file of its own
+    pub fn is_unique(&self) -> bool {
+        self.0.contains("{id}")
+    }
+
+    pub fn render(&self, item: &data::Item) -> String {
+        self.0
+            .replace("{id}", &item.id.to_string())
+            .replace("{name}", &slug(&item.name))
+    }
+}
+
+impl Default for FilenameTemplate {
+    fn default() -> Self {
+        FilenameTemplate(Self::DEFAULT.to_string())
+    }
+}
+
+/// Writes the listings of each item to its own CSV file
+pub struct CsvSink {
+    output: PathBuf,
+    template: FilenameTemplate,
+}
+
+impl CsvSink {
+    pub fn new(output: &Path, template: FilenameTemplate) -> Self {
+        Self {
+            output: output.to_path_buf(),
+            template,
+        }
+    }
+}
+
+impl OutputSink for CsvSink {
+    fn path(&self, item: &data::Item) -> PathBuf {
+        self.output.join(self.template.render(item))
+    }
+
+    fn write(&self, item: &data::Item, listings: &[ListingOutput]) -> Result<(), failure::Error> {
+        let mut wtr = csv::Writer::from_path(self.path(item))?;
+
+        for listing in listings {
+            wtr.serialize(listing)?;
+        }
+
+        Ok(())
+    }
+
+    fn written(&self, item: &data::Item) -> Result<Written, failure::Error> {
+        let mut written = Written::default();
+        let path = self.path(item);
+        if !path.exists() {
+            return Ok(written);
+        }
+
+        for listing in read_csv(&path)? {
+            written.add(listing.listing_type, listing.timestamp);
+        }
+        Ok(written)
+    }
+
+    fn append(&self, item: &data::Item, listings: &[ListingOutput]) -> Result<(), failure::Error> {
+        let path = self.path(item);
+        let file = OpenOptions::new().create(true).append(true).open(&path)?;
+        let is_empty = file.metadata()?.len() == 0;
+        let mut wtr = csv::WriterBuilder::new()
+            .has_headers(is_empty)
+            .from_writer(file);
+
+        for listing in listings {
+            wtr.serialize(listing)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Listing with the item it belongs to, for sinks that hold every item
+#[derive(Serialize, Debug)]
+struct ItemListingOutput<'a> {
+    item_id: u64,
+    item_name: &'a str,
+    #[serde(flatten)]
+    listing: &'a ListingOutput<'a>,
+}
+
+struct JsonLines {
+    file: BufWriter<File>,
+    /// By item ID
+    written: HashMap<u64, Written>,
+}
+
+/// Appends the listings of every item to a single JSON Lines file
+pub struct JsonLinesSink {
+    path: PathBuf,
+    state: Mutex<JsonLines>,
+}
+
+impl JsonLinesSink {
+    pub const FILENAME: &'static str = "listings.jsonl";
+
+    pub fn open(output: &Path, keep: bool) -> Result<Self, failure::Error> {
+        let path = output.join(Self::FILENAME);
+
+        let mut written: HashMap<u64, Written> = HashMap::new();
+        // Length of the file up to the end of its last complete line
+        let mut complete = 0;
+        if keep && path.exists() {
+            let mut reader = BufReader::new(File::open(&path)?);
+            let mut line = String::new();
+            let mut number = 0;
+            while reader.read_line(&mut line)? > 0 {
+                number += 1;
+                if !line.ends_with('\n') {
+                    warn!(
+                        "Dropping incomplete listing line {} in \"{}\"",
+                        number,
+                        path.to_str().unwrap_or("unknown")
+                    );
+                    break;
+                }
+                complete += line.len() as u64;
+
+                match serde_json::from_str::<StoredListing>(&line) {
+                    Ok(listing) => written
+                        .entry(listing.item_id)
+                        .or_default()
+                        .add(listing.listing_type, listing.timestamp),
+                    Err(e) => warn!(
+                        "Ignoring invalid listing line {} in \"{}\": {}",
+                        number,
+                        path.to_str().unwrap_or("unknown"),
+                        e
+                    ),
+                }
+                line.clear();
+            }
+        }
+
+        let file = OpenOptions::new()
+            .create(true)
+            .write(true)
+            .append(keep)
+            .truncate(!keep)
+            .open(&path)?;
+        // So that new lines are not joined onto one that was being written when the process died
+        if keep {
+            file.set_len(complete)?;
+        }
+
+        Ok(Self {
+            path,
+            state: Mutex::new(JsonLines {
+                file: BufWriter::new(file),
+                written,
+            }),
+        })
+    }
+}
+
+impl JsonLines {
+    fn append(
+        &mut self,
+        item: &data::Item,
+        listings: &[ListingOutput],
+    ) -> Result<(), failure::Error> {
+        let written = self.written.entry(item.id).or_default();
+        for listing in listings {
+            serde_json::to_writer(
+                &mut self.file,
+                &ItemListingOutput {
+                    item_id: item.id,
+                    item_name: &item.name,
+                    listing,
+                },
+            )?;
+            self.file.write_all(b"\n")?;
+            written.add(listing.listing_type, *listing.timestamp);
+        }
+        // Items are checkpointed once written, so they must not linger in the buffer
+        self.file.flush()?;
+        Ok(())
+    }
+
+    /// Remove the listings of the item that have been written to the file at `path`. The file is
+    /// rewritten, which only happens to items that were written before a resume.
+    fn remove(&mut self, path: &Path, item_id: u64) -> Result<(), failure::Error> {
+        match self.written.remove(&item_id) {
+            Some(written) if written.buy.count + written.sell.count > 0 => {}
+            _ => return Ok(()),
+        }
+        self.file.flush()?;
+
+        let partial = path.with_extension("jsonl.tmp");
+        {
+            let mut rewritten = BufWriter::new(File::create(&partial)?);
+            for line in BufReader::new(File::open(path)?).lines() {
+                let line = line?;
+                // Lines that cannot be read were warned about when the sink was opened
+                let other_item = serde_json::from_str::<StoredListing>(&line)
+                    .map(|listing| listing.item_id != item_id)
+                    .unwrap_or(true);
+                if other_item {
+                    rewritten.write_all(line.as_bytes())?;
+                    rewritten.write_all(b"\n")?;
+                }
+            }
+            rewritten.flush()?;
+        }
+        fs::rename(&partial, path)?;
+
+        self.file = BufWriter::new(OpenOptions::new().append(true).open(path)?);
+        Ok(())
+    }
+}
+
+impl OutputSink for JsonLinesSink {
+    fn path(&self, _item: &data::Item) -> PathBuf {
+        self.path.clone()
+    }
+
+    fn write(&self, item: &data::Item, listings: &[ListingOutput]) -> Result<(), failure::Error> {
+        let mut state = self.state.lock().expect("not to be poisoned");
+        state.remove(&self.path, item.id)?;
+        state.append(item, listings)
+    }
+
+    fn append(&self, item: &data::Item, listings: &[ListingOutput]) -> Result<(), failure::Error> {
+        let mut state = self.state.lock().expect("not to be poisoned");
+        state.append(item, listings)
+    }
+
+    fn written(&self, item: &data::Item) -> Result<Written, failure::Error> {
+        let state = self.state.lock().expect("not to be poisoned");
+        Ok(state.written.get(&item.id).cloned().unwrap_or_default())
+    }
+}
+
+#[cfg(feature = "parquet")]
+mod parquet_sink {
+    use super::{ListingOutput, OutputSink};
+    use failure;
+    use parquet::data_type::{ByteArray, ByteArrayType, Int64Type};
+    use parquet::file::properties::WriterProperties;
+    use parquet::file::writer::SerializedFileWriter;
+    use parquet::schema::parser::parse_message_type;
+    use spidy_scrapey::data;
+    use std::fs::File;
+    use std::mem;
+    use std::path::{Path, PathBuf};
+    use std::sync::{Arc, Mutex};
+
+    const SCHEMA: &str = "
+        message listing {
+            required int64 item_id (INTEGER(64, false));
+            required binary item_name (STRING);
+            required int64 timestamp (TIMESTAMP(MILLIS, true));
+            required binary type (STRING);
+            required int64 unit_price (INTEGER(64, false));
+            required int64 quantity (INTEGER(64, false));
+            required int64 listings (INTEGER(64, false));
+        }
+    ";
+
+    /// Number of listings to buffer before writing them out as a row group
+    const ROW_GROUP_SIZE: usize = 100_000;
+
+    /// Listings that have not been written yet, by column
+    #[derive(Default)]
+    struct Columns {
+        item_id: Vec<i64>,
+        item_name: Vec<ByteArray>,
+        timestamp: Vec<i64>,
+        listing_type: Vec<ByteArray>,
+        unit_price: Vec<i64>,
+        quantity: Vec<i64>,
+        listings: Vec<i64>,
+    }
+
+    /// Values of a column, by physical type
+    enum Values<'a> {
+        Int64(&'a [i64]),
+        ByteArray(&'a [ByteArray]),
+    }
+
+    impl Columns {
+        /// Values of the column named in `SCHEMA`
+        fn values(&self, name: &str) -> Option<Values<'_>> {
+            Some(match name {
+                "item_id" => Values::Int64(&self.item_id),
+                "item_name" => Values::ByteArray(&self.item_name),
+                "timestamp" => Values::Int64(&self.timestamp),
+                "type" => Values::ByteArray(&self.listing_type),
+                "unit_price" => Values::Int64(&self.unit_price),
+                "quantity" => Values::Int64(&self.quantity),
+                "listings" => Values::Int64(&self.listings),
+                _ => return None,
+            })
+        }
+    }
+
+    struct State {
+        writer: Option<SerializedFileWriter<File>>,
+        columns: Columns,
+    }
+
+    /// Writes the listings of every item to a single Apache Parquet file. The file is only
+    /// readable once the sink has been finished, so it cannot be added to.
+    pub struct ParquetSink {
+        path: PathBuf,
+        state: Mutex<State>,
+    }
+
+    impl ParquetSink {
+        pub const FILENAME: &'static str = "listings.parquet";
+
+        pub fn create(output: &Path, keep: bool) -> Result<Self, failure::Error> {
+            if keep {
+                return Err(failure::err_msg(
+                    "Parquet output cannot be resumed or updated incrementally",
+                ));
+            }
+
+            let path = output.join(Self::FILENAME);
+            let schema = Arc::new(parse_message_type(SCHEMA)?);
+            let properties = Arc::new(WriterProperties::builder().build());
+            let writer = SerializedFileWriter::new(File::create(&path)?, schema, properties)?;
+
+            Ok(Self {
+                path,
+                state: Mutex::new(State {
+                    writer: Some(writer),
+                    columns: Default::default(),
+                }),
+            })
+        }
+    }
+
+    impl State {
+        fn writer(&mut self) -> Result<&mut SerializedFileWriter<File>, failure::Error> {
+            self.writer
+                .as_mut()
+                .ok_or_else(|| failure::err_msg("Parquet output has already been finished"))
+        }
+
+        fn write_row_group(&mut self) -> Result<(), failure::Error> {
+            if self.columns.item_id.is_empty() {
+                return Ok(());
+            }
+            let columns = mem::take(&mut self.columns);
+            let writer = self.writer()?;
+            let names: Vec<String> = writer
+                .schema_descr()
+                .columns()
+                .iter()
+                .map(|column| column.name().to_string())
+                .collect();
+            let mut row_group = writer.next_row_group()?;
+
+            for name in &names {
+                let mut column = row_group
+                    .next_column()?
+                    .expect("a column writer for every column in the schema");
+                match columns.values(name) {
+                    Some(Values::Int64(values)) => {
+                        column
+                            .typed::<Int64Type>()
+                            .write_batch(values, None, None)?;
+                    }
+                    Some(Values::ByteArray(values)) => {
+                        column
+                            .typed::<ByteArrayType>()
+                            .write_batch(values, None, None)?;
+                    }
+                    None => {
+                        return Err(failure::err_msg(format!(
+                            "No values for Parquet column \"{}\"",
+                            name
+                        )))
+                    }
+                }
+                column.close()?;
+            }
+
+            row_group.close()?;
+            Ok(())
+        }
+    }
+
+    impl OutputSink for ParquetSink {
+        fn path(&self, _item: &data::Item) -> PathBuf {
+            self.path.clone()
+        }
+
+        fn write(
+            &self,
+            item: &data::Item,
+            listings: &[ListingOutput],
+        ) -> Result<(), failure::Error> {
+            let mut state = self.state.lock().expect("not to be poisoned");
+            {
+                let columns = &mut state.columns;
+                for listing in listings {
+                    columns.item_id.push(item.id as i64);
+                    columns.item_name.push(ByteArray::from(item.name.as_str()));
+                    columns.timestamp.push(listing.timestamp.timestamp_millis());
+                    columns
+                        .listing_type
+                        .push(ByteArray::from(listing.listing_type.to_string().as_str()));
+                    columns.unit_price.push(listing.unit_price as i64);
+                    columns.quantity.push(listing.quantity as i64);
+                    columns.listings.push(listing.listings as i64);
+                }
+            }
+
+            if state.columns.item_id.len() >= ROW_GROUP_SIZE {
+                state.write_row_group()?;
+            }
+            Ok(())
+        }
+
+        fn finish(&self) -> Result<(), failure::Error> {
+            let mut state = self.state.lock().expect("not to be poisoned");
+            state.write_row_group()?;
+            if let Some(writer) = state.writer.take() {
+                writer.close()?;
+            }
+            Ok(())
+        }
+    }
+}
+
+#[cfg(feature = "sqlite")]
+mod sqlite_sink {
+    use super::{ListingOutput, OutputSink, Written};
+    use chrono::{NaiveDateTime, TimeZone, Utc};
+    use failure;
+    use rusqlite::{self, Connection};
+    use spidy_scrapey::{api, data};
+    use std::path::{Path, PathBuf};
+    use std::sync::Mutex;
+
+    const SCHEMA: &str = "
+        CREATE TABLE IF NOT EXISTS items (
+            id INTEGER PRIMARY KEY,
+            name TEXT NOT NULL,
+            rarity INTEGER NOT NULL,
+            restriction_level INTEGER NOT NULL,
+            img TEXT NOT NULL,
+            price_last_changed TEXT NOT NULL,
+            max_offer_unit_price INTEGER NOT NULL,
+            min_sale_unit_price INTEGER NOT NULL,
+            offer_availability INTEGER NOT NULL,
+            sale_availability INTEGER NOT NULL,
+            sale_price_change_last_hour INTEGER NOT NULL,
+            offer_price_change_last_hour INTEGER NOT NULL,
+            type_id INTEGER NOT NULL,
+            sub_type_id INTEGER NOT NULL
+        );
+        CREATE TABLE IF NOT EXISTS listings (
+            item_id INTEGER NOT NULL REFERENCES items (id),
+            timestamp TEXT NOT NULL,
+            type TEXT NOT NULL,
+            unit_price INTEGER NOT NULL,
+            quantity INTEGER NOT NULL,
+            listings INTEGER NOT NULL
+        );
+        CREATE INDEX IF NOT EXISTS listings_item_id ON listings (item_id, timestamp);
+    ";
+
+    /// Timestamps are stored in UTC, in a format that SQLite's date functions understand
+    const TIMESTAMP_FORMAT: &str = "%F %T";
+
+    /// Writes items and their listings to a SQLite database
+    pub struct SqliteSink {
+        path: PathBuf,
+        connection: Mutex<Connection>,
+    }
+
+    impl SqliteSink {
+        pub const FILENAME: &'static str = "listings.sqlite";
+
+        /// Open the database, removing the items and listings of earlier runs unless `keep` is
+        /// set
+        pub fn open(output: &Path, keep: bool) -> Result<Self, failure::Error> {
+            let path = output.join(Self::FILENAME);
+            let connection = Connection::open(&path)?;
+            connection.execute_batch(SCHEMA)?;
+            if !keep {
+                connection.execute_batch("DELETE FROM listings; DELETE FROM items;")?;
+            }
+
+            Ok(Self {
+                path,
+                connection: Mutex::new(connection),
+            })
+        }
+
+        /// Insert or update the item and add its listings, first removing any existing
+        /// listings when `replace` is set
+        fn insert(
+            &self,
+            item: &data::Item,
+            listings: &[ListingOutput],
+            replace: bool,
+        ) -> Result<(), failure::Error> {
+            let mut connection = self.connection.lock().expect("not to be poisoned");
+            let transaction = connection.transaction()?;
+
+            transaction.execute(
+                "INSERT OR REPLACE INTO items VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)",
+                rusqlite::params![
+                    item.id as i64,
+                    item.name,
+                    item.rarity as i64,
+                    item.restriction_level,
+                    item.img,
+                    item.price_last_changed.format(TIMESTAMP_FORMAT).to_string(),
+                    item.max_offer_unit_price as i64,
+                    item.min_sale_unit_price as i64,
+                    item.offer_availability as i64,
+                    item.sale_availability as i64,
+                    item.sale_price_change_last_hour,
+                    item.offer_price_change_last_hour,
+                    item.type_id as i64,
+                    item.sub_type_id as i64,
+                ],
+            )?;
+            if replace {
+                transaction.execute(
+                    "DELETE FROM listings WHERE item_id = ?",
+                    rusqlite::params![item.id as i64],
+                )?;
+            }
+            {
+                let mut insert =
+                    transaction.prepare("INSERT INTO listings VALUES (?, ?, ?, ?, ?, ?)")?;
+                for listing in listings {
+                    insert.execute(rusqlite::params![
+                        item.id as i64,
+                        listing.timestamp.format(TIMESTAMP_FORMAT).to_string(),
+                        listing.listing_type.to_string(),
+                        listing.unit_price as i64,
+                        listing.quantity as i64,
+                        listing.listings as i64,
+                    ])?;
+                }
+            }
+
+            transaction.commit()?;
+            Ok(())
+        }
+    }
+
+    impl OutputSink for SqliteSink {
+        fn path(&self, _item: &data::Item) -> PathBuf {
+            self.path.clone()
+        }
+
+        fn write(
+            &self,
+            item: &data::Item,
+            listings: &[ListingOutput],
+        ) -> Result<(), failure::Error> {
+            self.insert(item, listings, true)
+        }
+
+        fn written(&self, item: &data::Item) -> Result<Written, failure::Error> {
+            let connection = self.connection.lock().expect("not to be poisoned");
+            let mut statement = connection.prepare(
+                "SELECT type, MAX(timestamp), COUNT(*) FROM listings WHERE item_id = ? \
+                 GROUP BY type",
+            )?;
+            let rows = statement.query_map(rusqlite::params![item.id as i64], |row| {
+                Ok((
+                    row.get::<_, String>(0)?,
+                    row.get::<_, String>(1)?,
+                    row.get::<_, i64>(2)?,
+                ))
+            })?;
+
+            let mut written = Written::default();
+            for row in rows {
+                let (listing_type, timestamp, count) = row?;
+                let listing_type = match listing_type.as_str() {
+                    "buy" => api::ListingType::Buy,
+                    "sell" => api::ListingType::Sell,
+                    other => {
+                        return Err(failure::err_msg(format!(
+                            "Unknown listing type \"{}\" in {}",
+                            other,
+                            self.path.to_str().unwrap_or("unknown")
+                        )))
+                    }
+                };
+                let timestamp = NaiveDateTime::parse_from_str(&timestamp, TIMESTAMP_FORMAT)?;
+                written
+                    .get_mut(listing_type)
+                    .add(Utc.from_utc_datetime(&timestamp), count as usize);
+            }
+            Ok(written)
+        }
+
+        fn append(
+            &self,
+            item: &data::Item,
+            listings: &[ListingOutput],
+        ) -> Result<(), failure::Error> {
+            self.insert(item, listings, false)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn slugs_names() {
+        assert_eq!(slug("Foo Bar"), "foo-bar");
+        assert_eq!(slug("Zojja's Breastplate"), "zojja-s-breastplate");
+        assert_eq!(slug("  ../../Étoile [Exotic]  "), "étoile-exotic");
+        assert_eq!(slug("???"), "item");
+    }
+
+    #[test]
+    fn parses_filename_templates() {
+        let unique = FilenameTemplate::parse("{id}-{name}.csv").unwrap();
+        let by_name = FilenameTemplate::parse("{name}.csv").unwrap();
+
+        assert!(unique.is_unique());
+        assert!(!by_name.is_unique());
+        assert!(FilenameTemplate::parse("items/{id}.csv").is_err());
+        assert!(FilenameTemplate::parse("{id}-{rarity}.csv").is_err());
+        assert!(FilenameTemplate::parse("{id.csv").is_err());
+        for invalid in &["", ".", ".."] {
+            assert!(FilenameTemplate::parse(invalid).is_err(), "{:?}", invalid);
+        }
+    }
+}
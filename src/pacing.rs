@@ -0,0 +1,359 @@
+//! Strategies for pacing requests to paginated APIs
+use backoff::backoff::Backoff;
+use backoff::ExponentialBackoff;
+use std::sync::{Arc, Mutex};
+use std::thread::sleep;
+use std::time::{Duration, Instant};
+
+/// Source of the current time, so that pacing can be tested without sleeping
+pub trait Clock: Send {
+    fn now(&self) -> Instant;
+}
+
+#[derive(Clone, Copy, Debug, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> Instant {
+        Instant::now()
+    }
+}
+
+/// Decides how long to wait before each request
+pub trait Pacer: Send {
+    /// Delay before making the next request
+    fn delay(&mut self) -> Duration;
+}
+
+/// Duration from `earlier` to `later`, or zero if `later` is not after `earlier`
+fn saturating_since(later: Instant, earlier: Instant) -> Duration {
+    if later > earlier {
+        later - earlier
+    } else {
+        Duration::new(0, 0)
+    }
+}
+
+/// Delay that grows exponentially with every request, up to `max_interval`
+pub struct Exponential {
+    backoff: ExponentialBackoff,
+}
+
+impl Exponential {
+    pub fn new(max_interval: Duration) -> Self {
+        Self::from_backoff(ExponentialBackoff {
+            max_interval,
+            // Pacing should carry on for as long as there are pages to fetch
+            max_elapsed_time: None,
+            ..Default::default()
+        })
+    }
+
+    pub fn from_backoff(backoff: ExponentialBackoff) -> Self {
+        Self { backoff }
+    }
+}
+
+impl Pacer for Exponential {
+    fn delay(&mut self) -> Duration {
+        // Randomisation can take the delay past the maximum, including the first one
+        self.backoff
+            .next_backoff()
+            .unwrap_or_else(|| Duration::new(0, 0))
+            .min(self.backoff.max_interval)
+    }
+}
+
+/// Requests are spaced at least `interval` apart
+pub struct FixedRate<C = SystemClock> {
+    interval: Duration,
+    clock: C,
+    last: Option<Instant>,
+}
+
+impl FixedRate {
+    pub fn new(interval: Duration) -> Self {
+        Self::with_clock(interval, SystemClock)
+    }
+}
+
+impl<C: Clock> FixedRate<C> {
+    pub fn with_clock(interval: Duration, clock: C) -> Self {
+        Self {
+            interval,
+            clock,
+            last: None,
+        }
+    }
+}
+
+impl<C: Clock> Pacer for FixedRate<C> {
+    fn delay(&mut self) -> Duration {
+        let now = self.clock.now();
+        let delay = match self.last {
+            Some(last) => saturating_since(last + self.interval, now),
+            None => Duration::new(0, 0),
+        };
+        self.last = Some(now + delay);
+        delay
+    }
+}
+
+/// Allows bursts of up to `burst` requests, refilled at `rate` requests per second
+pub struct TokenBucket<C = SystemClock> {
+    rate: f64,
+    burst: f64,
+    tokens: f64,
+    updated: Instant,
+    clock: C,
+}
+
+impl TokenBucket {
+    pub fn new(rate: f64, burst: u32) -> Self {
+        Self::with_clock(rate, burst, SystemClock)
+    }
+}
+
+impl<C: Clock> TokenBucket<C> {
+    pub fn with_clock(rate: f64, burst: u32, clock: C) -> Self {
+        let burst = f64::from(burst.max(1));
+        Self {
+            rate,
+            burst,
+            tokens: burst,
+            updated: clock.now(),
+            clock,
+        }
+    }
+}
+
+impl<C: Clock> Pacer for TokenBucket<C> {
+    fn delay(&mut self) -> Duration {
+        let now = self.clock.now();
+        let elapsed = duration_to_secs(saturating_since(now, self.updated));
+        self.tokens = (self.tokens + elapsed * self.rate).min(self.burst);
+
+        if self.tokens >= 1.0 {
+            self.updated = now;
+            self.tokens -= 1.0;
+            Duration::new(0, 0)
+        } else {
+            // Wait for the tokens already reserved by requests that are still waiting, then for
+            // the next token, which is consumed by this request
+            let pending = saturating_since(self.updated, now);
+            let delay = pending + secs_to_duration((1.0 - self.tokens) / self.rate);
+            self.updated = now + delay;
+            self.tokens = 0.0;
+            delay
+        }
+    }
+}
+
+fn duration_to_secs(duration: Duration) -> f64 {
+    duration.as_secs() as f64 + f64::from(duration.subsec_nanos()) / 1_000_000_000.0
+}
+
+fn secs_to_duration(secs: f64) -> Duration {
+    Duration::new(secs.trunc() as u64, (secs.fract() * 1_000_000_000.0) as u32)
+}
+
+/// Pacing strategy to use for each paginated request
+#[derive(Clone, Copy, Debug)]
+pub enum Pacing {
+    Exponential { max_interval: Duration },
+    FixedRate { interval: Duration },
+    TokenBucket { rate: f64, burst: u32 },
+}
+
+impl Pacing {
+    pub fn pacer(&self) -> Box<dyn Pacer> {
+        match *self {
+            Pacing::Exponential { max_interval } => Box::new(Exponential::new(max_interval)),
+            Pacing::FixedRate { interval } => Box::new(FixedRate::new(interval)),
+            Pacing::TokenBucket { rate, burst } => Box::new(TokenBucket::new(rate, burst)),
+        }
+    }
+}
+
+impl Default for Pacing {
+    fn default() -> Self {
+        Pacing::Exponential {
+            max_interval: Duration::from_secs(1),
+        }
+    }
+}
+
+/// Token bucket shared by every clone, limiting the rate of requests across the whole process
+#[derive(Clone)]
+pub struct RateLimiter<C = SystemClock> {
+    bucket: Arc<Mutex<TokenBucket<C>>>,
+}
+
+impl RateLimiter {
+    pub fn new(rate: f64, burst: u32) -> Self {
+        Self::with_clock(rate, burst, SystemClock)
+    }
+}
+
+impl<C: Clock> RateLimiter<C> {
+    pub fn with_clock(rate: f64, burst: u32, clock: C) -> Self {
+        Self {
+            bucket: Arc::new(Mutex::new(TokenBucket::with_clock(rate, burst, clock))),
+        }
+    }
+
+    /// Reserve a request, returning how long to wait before making it
+    pub fn reserve(&self) -> Duration {
+        self.bucket.lock().expect("not to be poisoned").delay()
+    }
+
+    /// Block until a request is allowed
+    pub fn wait(&self) {
+        // The token is reserved while holding the lock, but we sleep without it
+        let delay = self.reserve();
+        if delay > Duration::new(0, 0) {
+            debug!(
+                "Rate limited: sleeping {}.{} seconds",
+                delay.as_secs(),
+                delay.subsec_millis()
+            );
+            sleep(delay);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Clock that only moves when told to
+    #[derive(Clone)]
+    struct FakeClock {
+        start: Instant,
+        offset: Arc<Mutex<Duration>>,
+    }
+
+    impl FakeClock {
+        fn new() -> Self {
+            Self {
+                start: Instant::now(),
+                offset: Default::default(),
+            }
+        }
+
+        fn advance(&self, duration: Duration) {
+            *self.offset.lock().unwrap() += duration;
+        }
+    }
+
+    impl Clock for FakeClock {
+        fn now(&self) -> Instant {
+            self.start + *self.offset.lock().unwrap()
+        }
+    }
+
+    fn millis(millis: u64) -> Duration {
+        Duration::from_millis(millis)
+    }
+
+    #[test]
+    fn fixed_rate_spaces_requests() {
+        let clock = FakeClock::new();
+        let mut pacer = FixedRate::with_clock(millis(500), clock.clone());
+
+        assert_eq!(pacer.delay(), millis(0));
+        assert_eq!(pacer.delay(), millis(500));
+
+        // The second request happened after sleeping 500ms, then took 200ms
+        clock.advance(millis(700));
+        assert_eq!(pacer.delay(), millis(300));
+
+        clock.advance(millis(2000));
+        assert_eq!(pacer.delay(), millis(0));
+    }
+
+    #[test]
+    fn token_bucket_allows_bursts() {
+        let clock = FakeClock::new();
+        let mut pacer = TokenBucket::with_clock(2.0, 3, clock.clone());
+
+        assert_eq!(pacer.delay(), millis(0));
+        assert_eq!(pacer.delay(), millis(0));
+        assert_eq!(pacer.delay(), millis(0));
+        assert_eq!(pacer.delay(), millis(500));
+
+        // Sleep for the token we waited for, then for a second token
+        clock.advance(millis(1000));
+        assert_eq!(pacer.delay(), millis(0));
+        assert_eq!(pacer.delay(), millis(500));
+    }
+
+    #[test]
+    fn token_bucket_does_not_exceed_burst() {
+        let clock = FakeClock::new();
+        let mut pacer = TokenBucket::with_clock(1.0, 2, clock.clone());
+
+        clock.advance(Duration::from_secs(60));
+        assert_eq!(pacer.delay(), millis(0));
+        assert_eq!(pacer.delay(), millis(0));
+        assert_eq!(pacer.delay(), millis(1000));
+    }
+
+    #[test]
+    fn token_bucket_queues_waiting_requests() {
+        let clock = FakeClock::new();
+        let mut pacer = TokenBucket::with_clock(2.0, 1, clock.clone());
+
+        assert_eq!(pacer.delay(), millis(0));
+        // Neither request has been made yet, so each waits for its own token
+        assert_eq!(pacer.delay(), millis(500));
+        assert_eq!(pacer.delay(), millis(1000));
+
+        clock.advance(millis(1000));
+        assert_eq!(pacer.delay(), millis(500));
+    }
+
+    #[test]
+    fn rate_limiter_is_shared_between_clones() {
+        let clock = FakeClock::new();
+        let limiter = RateLimiter::with_clock(2.0, 2, clock.clone());
+        let other = limiter.clone();
+
+        assert_eq!(limiter.reserve(), millis(0));
+        assert_eq!(other.reserve(), millis(0));
+        assert_eq!(limiter.reserve(), millis(500));
+        assert_eq!(other.reserve(), millis(1000));
+
+        clock.advance(millis(1000));
+        assert_eq!(limiter.reserve(), millis(500));
+    }
+
+    #[test]
+    fn exponential_grows_up_to_the_max_interval() {
+        let mut pacer = Exponential::from_backoff(ExponentialBackoff {
+            initial_interval: millis(100),
+            current_interval: millis(100),
+            randomization_factor: 0.0,
+            multiplier: 2.0,
+            max_interval: millis(300),
+            max_elapsed_time: None,
+            ..Default::default()
+        });
+
+        assert_eq!(pacer.delay(), millis(100));
+        assert_eq!(pacer.delay(), millis(200));
+        assert_eq!(pacer.delay(), millis(300));
+        assert_eq!(pacer.delay(), millis(300));
+    }
+
+    #[test]
+    fn exponential_never_exceeds_the_max_interval() {
+        let mut none = Exponential::new(millis(0));
+        let mut capped = Exponential::new(millis(300));
+
+        for _ in 0..20 {
+            assert_eq!(none.delay(), millis(0));
+            assert!(capped.delay() <= millis(300));
+        }
+    }
+}
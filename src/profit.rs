@@ -0,0 +1,130 @@
+//! `profit` subcommand, which ranks recipes by the profit of crafting them
+use clap::{App, Arg, ArgMatches, SubCommand};
+use csv;
+use failure;
+use recipes;
+use spidy_scrapey::api;
+use spidy_scrapey::crafting::{Calculator, Profit, Trade};
+
+pub const FILENAME: &str = "profit.csv";
+
+#[derive(Serialize, Debug)]
+struct ProfitOutput<'a> {
+    rank: usize,
+    recipe_id: u64,
+    name: &'a str,
+    result_item_id: u64,
+    result_count: u64,
+    cost: u64,
+    revenue: u64,
+    profit: i64,
+    /// IDs of the ingredients to craft rather than buy, separated by `;`
+    crafted: String,
+}
+
+impl<'a> ProfitOutput<'a> {
+    fn from_profit(rank: usize, profit: &'a Profit) -> Self {
+        let crafted: Vec<String> = profit.crafted.iter().map(u64::to_string).collect();
+
+        Self {
+            rank,
+            recipe_id: profit.recipe.id,
+            name: &profit.recipe.name,
+            result_item_id: profit.recipe.result_item_id,
+            result_count: profit.recipe.result_count,
+            cost: profit.cost,
+            revenue: profit.revenue,
+            profit: profit.profit,
+            crafted: crafted.join(";"),
+        }
+    }
+}
+
+pub fn subcommand<'a, 'b>() -> App<'a, 'b>
+where
+    'a: 'b,
+{
+    SubCommand::with_name("profit")
+        .about(
+            "Rank recipes by the profit of crafting them and selling the result, after the \
+             trading post fee. Ingredients are crafted instead of bought when that is cheaper.",
+        ).arg(
+            Arg::with_name("discipline")
+                .help(
+                    "Crafting discipline, by ID or name. Defaults to every discipline. Only \
+                     recipes of the discipline are considered for crafting ingredients.",
+                ).long("--discipline")
+                .takes_value(true),
+        ).arg(
+            Arg::with_name("buy")
+                .help(
+                    "Buy ingredients `instant`ly from the cheapest sell listing, or with an \
+                     `order` at the highest buy order",
+                ).default_value("instant")
+                .possible_values(&["instant", "order"])
+                .long("--buy")
+                .takes_value(true),
+        ).arg(
+            Arg::with_name("sell")
+                .help(
+                    "Sell crafted items `instant`ly to the highest buy order, or with an `order` \
+                     at the cheapest sell listing",
+                ).default_value("instant")
+                .possible_values(&["instant", "order"])
+                .long("--sell")
+                .takes_value(true),
+        )
+}
+
+fn trade(args: &ArgMatches, name: &str) -> Trade {
+    match args.value_of(name).expect("Value to be present") {
+        "order" => Trade::Order,
+        _ => Trade::Instant,
+    }
+}
+
+pub fn run(api: &api::Api, args: &ArgMatches) -> Result<(), failure::Error> {
+    let discipline_id = recipes::discipline(api, args)?;
+    let path = ::output_dir(args)?.join(FILENAME);
+
+    // Only single recipes come with their ingredients
+    let recipes = api.recipes(discipline_id)?;
+    let total = recipes.len();
+    let recipes: Vec<_> = recipes
+        .into_iter()
+        .enumerate()
+        .filter_map(|(index, recipe)| {
+            info!(
+                "[{} of {}] Fetching ingredients for recipe \"{}\"",
+                index + 1,
+                total,
+                recipe.name
+            );
+            match api.recipe(recipe.id) {
+                Ok(recipe) => Some(recipe),
+                Err(e) => {
+                    warn!("Skipping recipe \"{}\": {}", recipe.name, e);
+                    None
+                }
+            }
+        }).collect();
+
+    info!("Fetching item prices");
+    let items = api.items()?;
+
+    let mut calculator = Calculator::new(&items, &recipes, trade(args, "buy"), trade(args, "sell"));
+    let profits = calculator.ranked(&recipes);
+
+    info!(
+        "Writing {} profitable recipes to \"{}\"",
+        profits.len(),
+        path.to_str().unwrap_or("unknown")
+    );
+    let mut wtr = csv::Writer::from_path(&path)?;
+    for (index, profit) in profits.iter().enumerate() {
+        wtr.serialize(ProfitOutput::from_profit(index + 1, profit))?;
+    }
+    wtr.flush()?;
+
+    Ok(())
+}
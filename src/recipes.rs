@@ -0,0 +1,113 @@
+//! `recipes` subcommand, which dumps crafting recipes to CSV
+use clap::{App, Arg, ArgMatches, SubCommand};
+use csv;
+use failure;
+use spidy_scrapey::{api, data};
+
+pub const FILENAME: &str = "recipes.csv";
+
+#[derive(Serialize, Debug)]
+struct RecipeOutput<'a> {
+    id: u64,
+    name: &'a str,
+    discipline_id: u64,
+    result_item_id: u64,
+    result_count: u64,
+    crafting_cost: u64,
+    min_sale_unit_price: u64,
+    max_offer_unit_price: u64,
+    rating: u64,
+    /// `item_id:count` pairs separated by `;`
+    ingredients: String,
+}
+
+impl<'a> RecipeOutput<'a> {
+    fn from_recipe(recipe: &'a data::Recipe) -> Self {
+        let ingredients: Vec<String> = recipe
+            .ingredients
+            .iter()
+            .map(|ingredient| format!("{}:{}", ingredient.item_id, ingredient.count))
+            .collect();
+
+        Self {
+            id: recipe.id,
+            name: &recipe.name,
+            discipline_id: recipe.discipline_id,
+            result_item_id: recipe.result_item_id,
+            result_count: recipe.result_count,
+            crafting_cost: recipe.crafting_cost,
+            min_sale_unit_price: recipe.min_sale_unit_price,
+            max_offer_unit_price: recipe.max_offer_unit_price,
+            rating: recipe.rating,
+            ingredients: ingredients.join(";"),
+        }
+    }
+}
+
+pub fn subcommand<'a, 'b>() -> App<'a, 'b>
+where
+    'a: 'b,
+{
+    SubCommand::with_name("recipes")
+        .about("Dump crafting recipes to a CSV file in the output directory")
+        .arg(
+            Arg::with_name("discipline")
+                .help("Crafting discipline, by ID or name. Defaults to every discipline.")
+                .long("--discipline")
+                .takes_value(true),
+        ).arg(
+            Arg::with_name("ingredients")
+                .help("Fetch every recipe individually to include its ingredients")
+                .long("--ingredients"),
+        )
+}
+
+/// Resolve `--discipline`, which is either an ID or a name, to an ID
+pub fn discipline(api: &api::Api, args: &ArgMatches) -> Result<Option<u64>, failure::Error> {
+    let discipline = match args.value_of("discipline") {
+        Some(discipline) => discipline,
+        None => return Ok(None),
+    };
+    if let Ok(id) = discipline.parse() {
+        return Ok(Some(id));
+    }
+
+    api.disciplines()?
+        .into_iter()
+        .find(|d| d.name.eq_ignore_ascii_case(discipline))
+        .map(|d| Some(d.id))
+        .ok_or_else(|| failure::err_msg(format!("Unknown discipline \"{}\"", discipline)))
+}
+
+pub fn run(api: &api::Api, args: &ArgMatches) -> Result<(), failure::Error> {
+    let discipline_id = discipline(api, args)?;
+    let path = ::output_dir(args)?.join(FILENAME);
+    let fetch_ingredients = args.is_present("ingredients");
+
+    info!(
+        "Writing recipes to \"{}\"",
+        path.to_str().unwrap_or("unknown")
+    );
+    let mut wtr = csv::Writer::from_path(&path)?;
+    let mut count = 0;
+
+    for recipe in api.recipes_lazy(discipline_id) {
+        let mut recipe = recipe?;
+        if fetch_ingredients {
+            match api.recipe(recipe.id) {
+                Ok(with_ingredients) => recipe = with_ingredients,
+                Err(e) => warn!(
+                    "Unable to fetch ingredients for recipe \"{}\" (ID {}): {}",
+                    recipe.name, recipe.id, e
+                ),
+            }
+        }
+
+        wtr.serialize(RecipeOutput::from_recipe(&recipe))?;
+        count += 1;
+    }
+    wtr.flush()?;
+
+    info!("Wrote {} recipes", count);
+    Ok(())
+}
@@ -0,0 +1,124 @@
+//! `spread` subcommand, which joins the buy and sell listings written by `listings` into one row
+//! per timestamp, with the spread between them
+use chrono::{DateTime, Utc};
+use clap::{App, Arg, ArgMatches, SubCommand};
+use failure;
+use output::{self, StoredListing};
+use spidy_scrapey::api;
+use spidy_scrapey::crafting::TRADING_POST_FEE;
+
+/// Best buy and sell listings at a point in time. Either side is carried forward from the last
+/// time it was listed, and is empty until then.
+#[derive(Serialize, Debug, PartialEq)]
+pub struct Spread {
+    #[serde(with = "::spidy_scrapey::custom_serde::timestamp")]
+    pub timestamp: DateTime<Utc>,
+    pub buy_price: Option<u64>,
+    pub buy_quantity: Option<u64>,
+    pub sell_price: Option<u64>,
+    pub sell_quantity: Option<u64>,
+    /// Sell price less buy price
+    pub spread: Option<i64>,
+    /// Proceeds of selling at the sell price, after the trading post fee, less the buy price
+    pub spread_after_fee: Option<i64>,
+}
+
+/// Join buy and sell listings, oldest first
+pub fn spread(listings: &[StoredListing]) -> Vec<Spread> {
+    let mut listings: Vec<&StoredListing> = listings.iter().collect();
+    listings.sort_by_key(|listing| listing.timestamp);
+
+    let mut buy: Option<&StoredListing> = None;
+    let mut sell: Option<&StoredListing> = None;
+    let mut rows: Vec<Spread> = vec![];
+    for listing in listings {
+        match listing.listing_type {
+            api::ListingType::Buy => buy = Some(listing),
+            api::ListingType::Sell => sell = Some(listing),
+        }
+
+        let (spread, spread_after_fee) = match (buy, sell) {
+            (Some(buy), Some(sell)) => {
+                let proceeds = sell.unit_price * (100 - TRADING_POST_FEE) / 100;
+                (
+                    Some(sell.unit_price as i64 - buy.unit_price as i64),
+                    Some(proceeds as i64 - buy.unit_price as i64),
+                )
+            }
+            _ => (None, None),
+        };
+        let row = Spread {
+            timestamp: listing.timestamp,
+            buy_price: buy.map(|buy| buy.unit_price),
+            buy_quantity: buy.map(|buy| buy.quantity),
+            sell_price: sell.map(|sell| sell.unit_price),
+            sell_quantity: sell.map(|sell| sell.quantity),
+            spread,
+            spread_after_fee,
+        };
+
+        // Buy and sell listings at the same time share a row
+        match rows.last_mut() {
+            Some(last) if last.timestamp == row.timestamp => *last = row,
+            _ => rows.push(row),
+        }
+    }
+    rows
+}
+
+pub fn subcommand<'a, 'b>() -> App<'a, 'b>
+where
+    'a: 'b,
+{
+    SubCommand::with_name("spread")
+        .about(
+            "Join the buy and sell listings in listing CSV files written by `listings` into one \
+             row per timestamp, with the best buy and sell price and quantity and the spread \
+             between them, before and after the trading post fee. Each file is written to the \
+             output directory as FILE.spread.csv.",
+        ).arg(
+            Arg::with_name("file")
+                .help("Listing CSV file to join")
+                .value_name("FILE")
+                .required(true)
+                .multiple(true),
+        )
+}
+
+pub fn run(args: &ArgMatches) -> Result<(), failure::Error> {
+    output::transform_files(args, "spread", spread)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use output::stored_listing as listing;
+    use spidy_scrapey::api::ListingType::{Buy, Sell};
+
+    #[test]
+    fn joins_buy_and_sell_listings() {
+        let listings = vec![
+            listing("2018-10-01T00:00:00Z", Buy, 100, 10),
+            listing("2018-10-02T00:00:00Z", Sell, 200, 10),
+            listing("2018-10-03T00:00:00Z", Buy, 120, 10),
+            listing("2018-10-03T00:00:00Z", Sell, 130, 10),
+        ];
+
+        let rows = spread(&listings);
+
+        assert_eq!(rows.len(), 3);
+        assert_eq!(
+            (rows[0].buy_price, rows[0].sell_price, rows[0].spread),
+            (Some(100), None, None)
+        );
+        assert_eq!(
+            (rows[1].buy_price, rows[1].sell_price, rows[1].spread),
+            (Some(100), Some(200), Some(100))
+        );
+        // 130 less the 15% fee is 110
+        assert_eq!(
+            (rows[2].spread, rows[2].spread_after_fee),
+            (Some(10), Some(-10))
+        );
+    }
+}
@@ -0,0 +1,162 @@
+//! Time window of the listings to fetch, from `--since` and `--until`
+use chrono::{DateTime, Duration, NaiveDate, TimeZone, Utc};
+use clap::{Arg, ArgMatches};
+use failure;
+
+pub fn args<'a, 'b>() -> Vec<Arg<'a, 'b>> {
+    vec![
+        Arg::with_name("since")
+            .help(
+                "Only include listings from this time onwards. Either an RFC 3339 timestamp \
+                 (`2018-10-01T00:00:00Z`), a date (`2018-10-01`) or a time relative to now \
+                 (`30d`, with `s`, `m`, `h`, `d` or `w`).",
+            ).long("--since")
+            .takes_value(true)
+            .validator(validate),
+        Arg::with_name("until")
+            .help("Only include listings up to this time, in the same formats as `--since`")
+            .long("--until")
+            .takes_value(true)
+            .validator(validate),
+    ]
+}
+
+fn validate(value: String) -> Result<(), String> {
+    parse_time(&value, Utc::now())
+        .map(|_| ())
+        .map_err(|e| e.to_string())
+}
+
+/// Parse an RFC 3339 timestamp, a date, or a time relative to `now` such as `30d`
+pub fn parse_time(value: &str, now: DateTime<Utc>) -> Result<DateTime<Utc>, failure::Error> {
+    if let Ok(time) = DateTime::parse_from_rfc3339(value) {
+        return Ok(time.with_timezone(&Utc));
+    }
+    if let Ok(date) = NaiveDate::parse_from_str(value, "%F") {
+        let midnight = date.and_hms_opt(0, 0, 0).expect("midnight to be valid");
+        return Ok(Utc.from_utc_datetime(&midnight));
+    }
+
+    let invalid = || {
+        failure::err_msg(format!(
+            "Invalid time \"{}\"; expected an RFC 3339 timestamp, a date or a relative time \
+             such as 30d",
+            value
+        ))
+    };
+    if value.len() < 2 || !value.is_char_boundary(value.len() - 1) {
+        return Err(invalid());
+    }
+    let (amount, unit) = value.split_at(value.len() - 1);
+    let amount: i64 = amount.parse().map_err(|_| invalid())?;
+    let unit_seconds = match unit {
+        "s" => 1,
+        "m" => 60,
+        "h" => 60 * 60,
+        "d" => 24 * 60 * 60,
+        "w" => 7 * 24 * 60 * 60,
+        _ => return Err(invalid()),
+    };
+
+    amount
+        .checked_mul(unit_seconds)
+        // The largest number of seconds a `Duration` can hold
+        .filter(|seconds| *seconds >= 0 && *seconds <= i64::MAX / 1000)
+        .and_then(|seconds| now.checked_sub_signed(Duration::seconds(seconds)))
+        .ok_or_else(invalid)
+}
+
+/// Listings within `since` and `until`, inclusive
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct Window {
+    pub since: Option<DateTime<Utc>>,
+    pub until: Option<DateTime<Utc>>,
+}
+
+impl Window {
+    pub fn from_args(args: &ArgMatches) -> Result<Self, failure::Error> {
+        let now = Utc::now();
+        let parse = |name| match args.value_of(name) {
+            Some(value) => parse_time(value, now).map(Some),
+            None => Ok(None),
+        };
+        let window = Self {
+            since: parse("since")?,
+            until: parse("until")?,
+        };
+
+        if let Window {
+            since: Some(since),
+            until: Some(until),
+        } = window
+        {
+            if since > until {
+                return Err(failure::err_msg(format!(
+                    "--since ({}) is after --until ({})",
+                    since, until
+                )));
+            }
+        }
+        Ok(window)
+    }
+
+    pub fn is_bounded(&self) -> bool {
+        self.since.is_some() || self.until.is_some()
+    }
+
+    /// Whether the time is before the start of the window
+    pub fn is_before(&self, time: DateTime<Utc>) -> bool {
+        Some(time) < self.since
+    }
+
+    pub fn contains(&self, time: DateTime<Utc>) -> bool {
+        let after = match self.until {
+            Some(until) => time > until,
+            None => false,
+        };
+        !self.is_before(time) && !after
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn utc(time: &str) -> DateTime<Utc> {
+        time.parse().unwrap()
+    }
+
+    #[test]
+    fn parses_times() {
+        let now = utc("2018-10-31T12:00:00Z");
+
+        assert_eq!(
+            parse_time("2018-10-01T06:00:00+02:00", now).unwrap(),
+            utc("2018-10-01T04:00:00Z")
+        );
+        assert_eq!(
+            parse_time("2018-10-01", now).unwrap(),
+            utc("2018-10-01T00:00:00Z")
+        );
+        assert_eq!(parse_time("30d", now).unwrap(), utc("2018-10-01T12:00:00Z"));
+        assert_eq!(parse_time("2w", now).unwrap(), utc("2018-10-17T12:00:00Z"));
+        assert_eq!(parse_time("90m", now).unwrap(), utc("2018-10-31T10:30:00Z"));
+        for invalid in &["", "d", "30", "30y", "-1d", "1é", "99999999999999999w"] {
+            assert!(parse_time(invalid, now).is_err(), "{}", invalid);
+        }
+    }
+
+    #[test]
+    fn contains_times_within_the_window() {
+        let window = Window {
+            since: Some(utc("2018-10-01T00:00:00Z")),
+            until: Some(utc("2018-10-02T00:00:00Z")),
+        };
+
+        assert!(window.is_before(utc("2018-09-30T23:59:59Z")));
+        assert!(window.contains(utc("2018-10-01T00:00:00Z")));
+        assert!(window.contains(utc("2018-10-02T00:00:00Z")));
+        assert!(!window.contains(utc("2018-10-02T00:00:01Z")));
+        assert!(Window::default().contains(utc("2000-01-01T00:00:00Z")));
+    }
+}
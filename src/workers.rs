@@ -0,0 +1,121 @@
+//! Bounded pool of worker threads
+use std::sync::mpsc::{sync_channel, SyncSender};
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread::{self, JoinHandle};
+
+/// Number of jobs submitted but not yet finished
+#[derive(Default)]
+struct Pending {
+    count: Mutex<usize>,
+    idle: Condvar,
+}
+
+impl Pending {
+    fn add(&self) {
+        *self.count.lock().expect("not to be poisoned") += 1;
+    }
+
+    fn done(&self) {
+        let mut count = self.count.lock().expect("not to be poisoned");
+        *count -= 1;
+        if *count == 0 {
+            self.idle.notify_all();
+        }
+    }
+
+    fn wait(&self) {
+        let mut count = self.count.lock().expect("not to be poisoned");
+        while *count > 0 {
+            count = self.idle.wait(count).expect("not to be poisoned");
+        }
+    }
+}
+
+/// Marks a job as done even if it panics
+struct Done<'a>(&'a Pending);
+
+impl<'a> Drop for Done<'a> {
+    fn drop(&mut self) {
+        self.0.done();
+    }
+}
+
+/// Handle to wait for every submitted job to finish
+#[derive(Clone)]
+pub struct Idle(Arc<Pending>);
+
+impl Idle {
+    pub fn wait(&self) {
+        self.0.wait()
+    }
+}
+
+/// Runs jobs on a fixed number of threads. Submitting blocks when every worker is busy and the
+/// queue is full.
+pub struct WorkerPool<T> {
+    sender: SyncSender<T>,
+    workers: Vec<JoinHandle<()>>,
+    pending: Arc<Pending>,
+}
+
+impl<T> WorkerPool<T>
+where
+    T: Send + 'static,
+{
+    pub fn new<F>(size: usize, f: F) -> Self
+    where
+        F: Fn(T) + Send + Sync + 'static,
+    {
+        let size = size.max(1);
+        let (sender, receiver) = sync_channel::<T>(size);
+        let receiver = Arc::new(Mutex::new(receiver));
+        let f = Arc::new(f);
+        let pending: Arc<Pending> = Default::default();
+
+        let workers = (0..size)
+            .map(|number| {
+                let receiver = Arc::clone(&receiver);
+                let f = Arc::clone(&f);
+                let pending = Arc::clone(&pending);
+
+                thread::Builder::new()
+                    .name(format!("worker-{}", number))
+                    .spawn(move || loop {
+                        let job = receiver.lock().expect("not to be poisoned").recv();
+                        match job {
+                            Ok(job) => {
+                                let _done = Done(&pending);
+                                f(job);
+                            }
+                            // The pool has been joined
+                            Err(_) => break,
+                        }
+                    }).expect("to spawn worker thread")
+            }).collect();
+
+        Self {
+            sender,
+            workers,
+            pending,
+        }
+    }
+
+    pub fn submit(&self, job: T) {
+        self.pending.add();
+        self.sender.send(job).expect("workers to be running");
+    }
+
+    pub fn idle(&self) -> Idle {
+        Idle(Arc::clone(&self.pending))
+    }
+
+    /// Wait for every submitted job to finish and stop the workers
+    pub fn join(self) {
+        drop(self.sender);
+        for worker in self.workers {
+            if worker.join().is_err() {
+                error!("Worker thread panicked");
+            }
+        }
+    }
+}
@@ -0,0 +1,394 @@
+#[macro_use]
+extern crate serde_json;
+extern crate spidy_scrapey;
+
+mod common;
+
+use common::{
+    item, item_result, items_page, listing, listings_page, recipe, recipe_result, types,
+    MockServer, Response,
+};
+use spidy_scrapey::{
+    Api, ApiBuilder, ApiFormat, Error, Ingredient, ListingType, Pacing, Rarity, RarityName, Retry,
+    TypeNames,
+};
+use std::time::Duration;
+
+fn builder(server: &MockServer) -> ApiBuilder {
+    Api::builder()
+        .base_url(server.base_url())
+        .format(ApiFormat::Json)
+        .pacing(Pacing::FixedRate {
+            interval: Duration::new(0, 0),
+        }).retry(Retry {
+            max_attempts: 2,
+            max_interval: Duration::from_millis(10),
+            ..Default::default()
+        })
+}
+
+#[test]
+fn fetches_item() {
+    let server = MockServer::start();
+    server.json("/v0.9/json/item/1", &item_result(1, "Foo"));
+
+    let api = builder(&server).build().unwrap();
+    let item = api.item(1).unwrap();
+
+    assert_eq!(item.id, 1);
+    assert_eq!(item.name, "Foo");
+    assert_eq!(item.rarity, Rarity::Common);
+}
+
+#[test]
+fn lazily_paginates_search_results() {
+    let server = MockServer::start();
+    server
+        .json(
+            "/v0.9/json/item-search/Foo/1",
+            &items_page(1, 2, vec![item(1, "Foo"), item(2, "Foo Bar")]),
+        ).json(
+            "/v0.9/json/item-search/Foo/2",
+            &items_page(2, 2, vec![item(3, "Foo Baz")]),
+        );
+
+    let api = builder(&server).build().unwrap();
+    let mut items = api.item_search_lazy("Foo");
+
+    assert_eq!(items.next().unwrap().unwrap().id, 1);
+    assert_eq!(server.request_count("/v0.9/json/item-search/Foo/2"), 0);
+
+    let rest: Vec<u64> = items.map(|item| item.unwrap().id).collect();
+    assert_eq!(rest, vec![2, 3]);
+    assert_eq!(server.request_count("/v0.9/json/item-search/Foo/2"), 1);
+}
+
+#[test]
+fn fetches_items_by_type() {
+    let server = MockServer::start();
+    server
+        .json(
+            "/v0.9/json/items/5/1",
+            &items_page(1, 2, vec![item(1, "Foo")]),
+        ).json(
+            "/v0.9/json/items/5/2",
+            &items_page(2, 2, vec![item(2, "Bar")]),
+        );
+
+    let api = builder(&server).build().unwrap();
+    let eager: Vec<u64> = api
+        .items_by_type(5)
+        .unwrap()
+        .iter()
+        .map(|item| item.id)
+        .collect();
+    let lazy: Vec<u64> = api
+        .items_by_type_lazy(5)
+        .map(|item| item.unwrap().id)
+        .collect();
+
+    assert_eq!(eager, vec![1, 2]);
+    assert_eq!(lazy, eager);
+    assert_eq!(server.request_count("/v0.9/json/items/all/1"), 0);
+}
+
+#[test]
+fn fetches_every_page_of_listings() {
+    let server = MockServer::start();
+    server
+        .json(
+            "/v0.9/json/listings/1/sell/1",
+            &listings_page("sell", 1, 2, vec![listing(common::TIMESTAMP, 120)]),
+        ).json(
+            "/v0.9/json/listings/1/sell/2",
+            &listings_page("sell", 2, 2, vec![listing(common::TIMESTAMP, 130)]),
+        );
+
+    let api = builder(&server).build().unwrap();
+    let listings = api.listings(1, ListingType::Sell).unwrap();
+
+    let prices: Vec<u64> = listings.iter().map(|listing| listing.unit_price).collect();
+    assert_eq!(prices, vec![120, 130]);
+}
+
+#[test]
+fn stops_paginating_listings_at_the_first_match() {
+    let server = MockServer::start();
+    server
+        .json(
+            "/v0.9/json/listings/1/sell/1",
+            &listings_page(
+                "sell",
+                1,
+                3,
+                vec![
+                    listing(common::TIMESTAMP, 120),
+                    listing(common::TIMESTAMP, 110),
+                ],
+            ),
+        ).json(
+            "/v0.9/json/listings/1/sell/2",
+            &listings_page("sell", 2, 3, vec![listing(common::TIMESTAMP, 100)]),
+        ).json(
+            "/v0.9/json/listings/1/sell/3",
+            &listings_page("sell", 3, 3, vec![listing(common::TIMESTAMP, 90)]),
+        );
+
+    let api = builder(&server).build().unwrap();
+    let mut listings = api
+        .listings_lazy(1, ListingType::Sell)
+        .until(|listing| listing.unit_price <= 100);
+
+    let prices: Vec<u64> = listings
+        .by_ref()
+        .map(|listing| listing.unwrap().unit_price)
+        .collect();
+    assert_eq!(prices, vec![120, 110]);
+    assert!(listings.next().is_none());
+    assert_eq!(server.request_count("/v0.9/json/listings/1/sell/2"), 1);
+    assert_eq!(server.request_count("/v0.9/json/listings/1/sell/3"), 0);
+}
+
+#[test]
+fn reports_the_failed_page() {
+    let server = MockServer::start();
+    server
+        .json(
+            "/v0.9/json/items/all/1",
+            &items_page(1, 2, vec![item(1, "Foo")]),
+        ).route("/v0.9/json/items/all/2", Response::status(500));
+
+    let api = builder(&server).build().unwrap();
+    let error = api.items().unwrap_err();
+
+    assert_eq!(error.page(), Some(2));
+    assert_eq!(error.status().map(|status| status.as_u16()), Some(500));
+    assert_eq!(server.request_count("/v0.9/json/items/all/2"), 2);
+}
+
+#[test]
+fn decodes_csv_item_like_json() {
+    let server = MockServer::start();
+    server
+        .json("/v0.9/json/item/1", &item_result(1, "Foo, the \"Bar\""))
+        .csv("/v0.9/csv/item/1", &[item(1, "Foo, the \"Bar\"")]);
+
+    let json = builder(&server).build().unwrap().item(1).unwrap();
+    let csv = builder(&server)
+        .format(ApiFormat::Csv)
+        .build()
+        .unwrap()
+        .item(1)
+        .unwrap();
+
+    assert_eq!(csv, json);
+}
+
+#[test]
+fn decodes_csv_listings_like_json() {
+    let listings = vec![
+        listing("2018-10-02 00:00:00 UTC", 120),
+        listing("2018-10-01 00:00:00 UTC", 130),
+    ];
+    let server = MockServer::start();
+    server
+        .json(
+            "/v0.9/json/listings/1/buy/1",
+            &listings_page("buy", 1, 1, listings.clone()),
+        ).csv("/v0.9/csv/listings/1/buy/1", &listings);
+
+    let json = builder(&server)
+        .build()
+        .unwrap()
+        .listings(1, ListingType::Buy)
+        .unwrap();
+    let csv = builder(&server)
+        .format(ApiFormat::Csv)
+        .build()
+        .unwrap()
+        .listings(1, ListingType::Buy)
+        .unwrap();
+
+    assert_eq!(csv.len(), 2);
+    assert_eq!(csv, json);
+}
+
+#[test]
+fn treats_csv_responses_as_the_last_page() {
+    let server = MockServer::start();
+    server.csv("/v0.9/csv/items/all/1", &[item(1, "Foo"), item(2, "Bar")]);
+
+    let api = builder(&server).format(ApiFormat::Csv).build().unwrap();
+    let items: Vec<u64> = api.items_lazy().map(|item| item.unwrap().id).collect();
+
+    assert_eq!(items, vec![1, 2]);
+    assert_eq!(server.requests(), vec!["/v0.9/csv/items/all/1"]);
+}
+
+#[test]
+fn reports_unknown_rarity_in_csv() {
+    let server = MockServer::start();
+    let mut unknown = item(1, "Foo");
+    unknown["rarity"] = json!(42);
+    server.csv("/v0.9/csv/item/1", &[unknown]);
+
+    let api = builder(&server).format(ApiFormat::Csv).build().unwrap();
+    match api.item(1) {
+        Err(Error::UnknownRarity { .. }) => {}
+        result => panic!("expected an unknown rarity, got {:?}", result),
+    }
+}
+
+#[test]
+fn reports_invalid_timestamps() {
+    let server = MockServer::start();
+    let mut invalid = item(1, "Foo");
+    invalid["price_last_changed"] = json!("yesterday");
+    // Named like the enum, which must not make it an unknown rarity
+    invalid["name"] = json!("Rarity");
+    server.json("/v0.9/json/item/1", &json!({ "result": invalid }));
+
+    let api = builder(&server).build().unwrap();
+    match api.item(1) {
+        Err(Error::InvalidTimestamp { .. }) => {}
+        result => panic!("expected an invalid timestamp, got {:?}", result),
+    }
+}
+
+#[test]
+fn fetches_recipes() {
+    let server = MockServer::start();
+    server
+        .json(
+            "/v0.9/json/recipes/1/1",
+            &items_page(1, 2, vec![recipe(1, "Foo")]),
+        ).json(
+            "/v0.9/json/recipes/1/2",
+            &items_page(2, 2, vec![recipe(2, "Bar")]),
+        ).json("/v0.9/json/recipes/all/1", &items_page(1, 1, vec![]))
+        .json(
+            "/v0.9/json/recipe/1",
+            &recipe_result(1, "Foo", &[(10, 2), (11, 1)]),
+        );
+
+    let api = builder(&server).build().unwrap();
+    let recipes = api.recipes(Some(1)).unwrap();
+    let recipe = api.recipe(1).unwrap();
+
+    assert_eq!(recipes.len(), 2);
+    assert_eq!(recipes[1].name, "Bar");
+    assert!(recipes[0].ingredients.is_empty());
+    assert_eq!(api.recipes_lazy(None).count(), 0);
+    assert_eq!(recipe.result_item_id, 1001);
+    assert_eq!(
+        recipe.ingredients,
+        vec![
+            Ingredient {
+                item_id: 10,
+                count: 2
+            },
+            Ingredient {
+                item_id: 11,
+                count: 1
+            },
+        ]
+    );
+}
+
+#[test]
+fn decodes_csv_gem_price_like_json() {
+    let server = MockServer::start();
+    server
+        .json(
+            "/v0.9/json/gem-price",
+            &json!({ "result": { "gem_to_gold": 1500, "gold_to_gem": 2500 } }),
+        ).csv(
+            "/v0.9/csv/gem-price",
+            &[json!({ "gem_to_gold": 1500, "gold_to_gem": 2500 })],
+        );
+
+    let json = builder(&server).build().unwrap().gem_price().unwrap();
+    let csv = builder(&server)
+        .format(ApiFormat::Csv)
+        .build()
+        .unwrap()
+        .gem_price()
+        .unwrap();
+
+    assert_eq!(json.gem_to_gold, 1500);
+    assert_eq!(json.gold_to_gem, 2500);
+    assert_eq!(csv, json);
+}
+
+#[test]
+fn decodes_csv_types_like_json() {
+    let server = MockServer::start();
+    server.json("/v0.9/json/types", &types()).csv(
+        "/v0.9/csv/types",
+        &[
+            json!({ "id": 5, "name": "Consumable", "parent_id": null }),
+            json!({ "id": 0, "name": "Sword", "parent_id": 18 }),
+            json!({ "id": 18, "name": "Weapon", "parent_id": null }),
+            json!({ "id": 6, "name": "Greatsword", "parent_id": 18 }),
+        ],
+    );
+
+    let json = builder(&server).build().unwrap().types().unwrap();
+    let csv = builder(&server)
+        .format(ApiFormat::Csv)
+        .build()
+        .unwrap()
+        .types()
+        .unwrap();
+
+    assert_eq!(json.len(), 2);
+    assert_eq!(json[1].sub_types[1].name, "Greatsword");
+    assert_eq!(csv, json);
+}
+
+#[test]
+fn fetches_disciplines_and_rarities() {
+    let server = MockServer::start();
+    server
+        .json(
+            "/v0.9/json/disciplines",
+            &json!({ "results": [{ "id": 1, "name": "Huntsman" }] }),
+        ).json(
+            "/v0.9/json/rarities",
+            &json!({ "results": [{ "id": 0, "name": "Junk" }, { "id": 1, "name": "Basic" }] }),
+        );
+
+    let api = builder(&server).build().unwrap();
+    let disciplines = api.disciplines().unwrap();
+    let rarities = api.rarities().unwrap();
+
+    assert_eq!(disciplines[0].name, "Huntsman");
+    assert_eq!(
+        rarities[1],
+        RarityName {
+            id: 1,
+            name: "Basic".to_string()
+        }
+    );
+}
+
+#[test]
+fn resolves_type_names() {
+    let server = MockServer::start();
+    server.json("/v0.9/json/types", &types());
+    let type_names = builder(&server).build().unwrap().type_names().unwrap();
+
+    let mut greatsword = item(1, "Foo");
+    greatsword["type_id"] = json!(18);
+    greatsword["sub_type_id"] = json!(6);
+    let greatsword = serde_json::from_value(greatsword).unwrap();
+    let consumable = serde_json::from_value(item(2, "Bar")).unwrap();
+    let mut unknown = item(3, "Baz");
+    unknown["type_id"] = json!(99);
+    let unknown = serde_json::from_value(unknown).unwrap();
+
+    assert_eq!(type_names.describe(&greatsword), "Weapon / Greatsword");
+    assert_eq!(type_names.describe(&consumable), "Consumable");
+    assert_eq!(type_names.describe(&unknown), "Type 99");
+    assert_eq!(TypeNames::default().type_name(18), None);
+}
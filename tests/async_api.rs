@@ -0,0 +1,219 @@
+#![cfg(feature = "async")]
+#[macro_use]
+extern crate serde_json;
+extern crate futures;
+extern crate spidy_scrapey;
+extern crate tokio;
+
+mod common;
+
+use common::{
+    item, item_result, items_page, listing, listings_page, recipe, recipe_result, types,
+    MockServer, Response,
+};
+use futures::{Future, Stream};
+use spidy_scrapey::{Api, ApiBuilder, ApiFormat, ListingType, Pacing, Retry};
+use std::time::Duration;
+
+fn builder(server: &MockServer) -> ApiBuilder {
+    Api::builder()
+        .base_url(server.base_url())
+        .format(ApiFormat::Json)
+        .pacing(Pacing::FixedRate {
+            interval: Duration::new(0, 0),
+        }).retry(Retry {
+            max_attempts: 2,
+            max_interval: Duration::from_millis(10),
+            ..Default::default()
+        })
+}
+
+/// Run a future to completion on a new runtime
+fn run<F>(future: F) -> Result<F::Item, F::Error>
+where
+    F: Future + Send + 'static,
+    F::Item: Send + 'static,
+    F::Error: Send + 'static,
+{
+    let mut runtime = tokio::runtime::Runtime::new().unwrap();
+    let result = runtime.block_on(future);
+    runtime.shutdown_now().wait().unwrap();
+    result
+}
+
+#[test]
+fn streams_search_results_asynchronously() {
+    let server = MockServer::start();
+    server
+        .json(
+            "/v0.9/json/item-search/Foo/1",
+            &items_page(1, 2, vec![item(1, "Foo")]),
+        ).json(
+            "/v0.9/json/item-search/Foo/2",
+            &items_page(2, 2, vec![item(2, "Foo Bar")]),
+        );
+
+    let api = builder(&server).build_async().unwrap();
+    let items = run(api.item_search("Foo").map(|item| item.id).collect()).unwrap();
+
+    assert_eq!(items, vec![1, 2]);
+}
+
+#[test]
+fn fetches_item_asynchronously() {
+    let server = MockServer::start();
+    server.json("/v0.9/json/item/1", &item_result(1, "Foo"));
+
+    let api = builder(&server).build_async().unwrap();
+    let item = run(api.item(1)).unwrap();
+
+    assert_eq!((item.id, item.name.as_str()), (1, "Foo"));
+}
+
+#[test]
+fn streams_every_page_of_listings() {
+    let server = MockServer::start();
+    server
+        .json(
+            "/v0.9/json/listings/1/sell/1",
+            &listings_page("sell", 1, 2, vec![listing(common::TIMESTAMP, 120)]),
+        ).json(
+            "/v0.9/json/listings/1/sell/2",
+            &listings_page("sell", 2, 2, vec![listing(common::TIMESTAMP, 130)]),
+        );
+
+    let api = builder(&server).build_async().unwrap();
+    let listings = api.listings(1, ListingType::Sell);
+    let prices = run(listings.map(|listing| listing.unit_price).collect()).unwrap();
+
+    assert_eq!(prices, vec![120, 130]);
+}
+
+#[test]
+fn streams_all_items_and_items_by_type() {
+    let server = MockServer::start();
+    server
+        .json(
+            "/v0.9/json/items/all/1",
+            &items_page(1, 2, vec![item(1, "Foo")]),
+        ).json(
+            "/v0.9/json/items/all/2",
+            &items_page(2, 2, vec![item(2, "Bar")]),
+        ).json(
+            "/v0.9/json/items/18/1",
+            &items_page(1, 1, vec![item(3, "Baz")]),
+        );
+
+    let api = builder(&server).build_async().unwrap();
+    let all = run(api.items().map(|item| item.id).collect()).unwrap();
+    let by_type = run(api.items_by_type(18).map(|item| item.id).collect()).unwrap();
+
+    assert_eq!(all, vec![1, 2]);
+    assert_eq!(by_type, vec![3]);
+}
+
+#[test]
+fn fetches_recipes_asynchronously() {
+    let server = MockServer::start();
+    server
+        .json(
+            "/v0.9/json/recipes/1/1",
+            &items_page(1, 2, vec![recipe(1, "Foo")]),
+        ).json(
+            "/v0.9/json/recipes/1/2",
+            &items_page(2, 2, vec![recipe(2, "Bar")]),
+        ).json("/v0.9/json/recipes/all/1", &items_page(1, 1, vec![]))
+        .json(
+            "/v0.9/json/recipe/1",
+            &recipe_result(1, "Foo", &[(10, 2), (11, 1)]),
+        );
+
+    let api = builder(&server).build_async().unwrap();
+    let recipes = run(api.recipes(Some(1)).map(|recipe| recipe.id).collect()).unwrap();
+    let all = run(api.recipes(None).collect()).unwrap();
+    let recipe = run(api.recipe(1)).unwrap();
+
+    assert_eq!(recipes, vec![1, 2]);
+    assert!(all.is_empty());
+    assert_eq!(recipe.ingredients.len(), 2);
+}
+
+#[test]
+fn fetches_unpaginated_results_asynchronously() {
+    let server = MockServer::start();
+    server
+        .json(
+            "/v0.9/json/gem-price",
+            &json!({ "result": { "gem_to_gold": 1500, "gold_to_gem": 2500 } }),
+        ).json("/v0.9/json/types", &types())
+        .json(
+            "/v0.9/json/disciplines",
+            &json!({ "results": [{ "id": 1, "name": "Huntsman" }] }),
+        ).json(
+            "/v0.9/json/rarities",
+            &json!({ "results": [{ "id": 0, "name": "Junk" }, { "id": 1, "name": "Basic" }] }),
+        );
+
+    let api = builder(&server).build_async().unwrap();
+    let gem_price = run(api.gem_price()).unwrap();
+    let types = run(api.types()).unwrap();
+    let type_names = run(api.type_names()).unwrap();
+    let disciplines = run(api.disciplines()).unwrap();
+    let rarities = run(api.rarities()).unwrap();
+
+    assert_eq!((gem_price.gem_to_gold, gem_price.gold_to_gem), (1500, 2500));
+    assert_eq!(types[1].sub_types[1].name, "Greatsword");
+    assert_eq!(type_names.type_name(18), Some("Weapon"));
+    assert_eq!(disciplines[0].name, "Huntsman");
+    assert_eq!(rarities[1].name, "Basic");
+}
+
+#[test]
+fn retries_failed_requests_asynchronously() {
+    let server = MockServer::start();
+    server
+        .route("/v0.9/json/item/1", Response::status(503))
+        .json("/v0.9/json/item/1", &item_result(1, "Foo"));
+
+    let api = builder(&server).build_async().unwrap();
+    let item = run(api.item(1)).unwrap();
+
+    assert_eq!(item.id, 1);
+    assert_eq!(server.request_count("/v0.9/json/item/1"), 2);
+}
+
+#[test]
+fn reports_the_failed_page_asynchronously() {
+    let server = MockServer::start();
+    server
+        .json(
+            "/v0.9/json/items/all/1",
+            &items_page(1, 2, vec![item(1, "Foo")]),
+        ).route("/v0.9/json/items/all/2", Response::status(500));
+
+    let api = builder(&server).build_async().unwrap();
+    let error = run(api.items().collect()).unwrap_err();
+
+    assert_eq!(error.page(), Some(2));
+    assert_eq!(
+        error.url(),
+        Some(format!("{}/v0.9/json/items/all/2", server.base_url()).as_str())
+    );
+    assert_eq!(error.status().map(|status| status.as_u16()), Some(500));
+    assert_eq!(server.request_count("/v0.9/json/items/all/2"), 2);
+}
+
+#[test]
+fn does_not_retry_decoding_errors_asynchronously() {
+    let server = MockServer::start();
+    server.route("/v0.9/json/item/1", Response::json(&json!({ "result": 1 })));
+
+    let api = builder(&server).build_async().unwrap();
+    let error = run(api.item(1)).unwrap_err();
+
+    assert_eq!(
+        error.url(),
+        Some(format!("{}/v0.9/json/item/1", server.base_url()).as_str())
+    );
+    assert_eq!(server.request_count("/v0.9/json/item/1"), 1);
+}
@@ -0,0 +1,1416 @@
+#[macro_use]
+extern crate serde_json;
+#[cfg(feature = "parquet")]
+extern crate parquet;
+#[cfg(feature = "sqlite")]
+extern crate rusqlite;
+extern crate tempfile;
+
+mod common;
+
+use common::{
+    item, item_result, items_page, listing, listings_page, recipe, recipe_result, types,
+    MockServer, Response,
+};
+use std::fs;
+use std::io::Write;
+use std::path::Path;
+use std::process::{Command, Output, Stdio};
+use std::thread;
+use std::time::{Duration, Instant};
+
+fn binary(server: &MockServer, output: &Path, args: &[&str]) -> Command {
+    let mut command = Command::new(env!("CARGO_BIN_EXE_spidy-scrapey"));
+    command
+        .args(args)
+        .arg("--base-url")
+        .arg(server.base_url())
+        .arg("--max-backoff")
+        .arg("0")
+        .arg("--output")
+        .arg(output);
+    command
+}
+
+fn command(server: &MockServer, output: &Path, args: &[&str]) -> Output {
+    binary(server, output, args)
+        .output()
+        .expect("to run binary")
+}
+
+fn run(server: &MockServer, output: &Path, args: &[&str]) -> Output {
+    let output = command(server, output, args);
+
+    assert!(
+        output.status.success(),
+        "binary failed: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    output
+}
+
+fn read_rows(path: &Path) -> Vec<String> {
+    fs::read_to_string(path)
+        .expect("output file to exist")
+        .lines()
+        .map(str::to_string)
+        .collect()
+}
+
+/// Number of CSV files in the directory, other than the manifest
+fn csv_files(path: &Path) -> usize {
+    fs::read_dir(path)
+        .expect("output directory to exist")
+        .filter(|entry| {
+            let path = entry.as_ref().expect("directory entry").path();
+            path.extension() == Some("csv".as_ref()) && !path.ends_with("items.csv")
+        }).count()
+}
+
+fn serve_listings(server: &MockServer, id: u64) {
+    server
+        .json(
+            &format!("/v0.9/json/listings/{}/buy/1", id),
+            &listings_page(
+                "buy",
+                1,
+                2,
+                vec![
+                    listing("2018-10-04 00:00:00 UTC", 104),
+                    listing("2018-10-03 00:00:00 UTC", 103),
+                ],
+            ),
+        ).json(
+            &format!("/v0.9/json/listings/{}/buy/2", id),
+            &listings_page("buy", 2, 2, vec![listing("2018-10-01 00:00:00 UTC", 101)]),
+        ).json(
+            &format!("/v0.9/json/listings/{}/sell/1", id),
+            &listings_page("sell", 1, 1, vec![listing("2018-10-02 00:00:00 UTC", 202)]),
+        );
+}
+
+#[test]
+fn fetches_listings_for_item_id() {
+    let server = MockServer::start();
+    server.json("/v0.9/json/item/1", &item_result(1, "Foo"));
+    serve_listings(&server, 1);
+
+    let output = tempfile::tempdir().expect("temporary directory");
+    run(&server, output.path(), &["listings", "--item-id", "1"]);
+
+    assert_eq!(
+        read_rows(&output.path().join("1-foo.csv")),
+        vec![
+            "timestamp,type,unit_price,quantity,listings",
+            "2018-10-01 00:00:00 UTC,buy,101,10,1",
+            "2018-10-02 00:00:00 UTC,sell,202,10,1",
+            "2018-10-03 00:00:00 UTC,buy,103,10,1",
+            "2018-10-04 00:00:00 UTC,buy,104,10,1",
+        ]
+    );
+    assert_eq!(server.request_count("/v0.9/json/listings/1/buy/1"), 1);
+    assert_eq!(server.request_count("/v0.9/json/listings/1/buy/2"), 1);
+    assert_eq!(server.request_count("/v0.9/json/listings/1/sell/1"), 1);
+}
+
+#[test]
+fn fetches_listings_in_csv_format() {
+    let server = MockServer::start();
+    server
+        .csv("/v0.9/csv/item/1", &[item(1, "Foo")])
+        .csv(
+            "/v0.9/csv/listings/1/buy/1",
+            &[listing("2018-10-01 00:00:00 UTC", 101)],
+        ).csv(
+            "/v0.9/csv/listings/1/sell/1",
+            &[listing("2018-10-02 00:00:00 UTC", 202)],
+        );
+
+    let output = tempfile::tempdir().expect("temporary directory");
+    run(
+        &server,
+        output.path(),
+        &["listings", "--api-format", "csv", "--item-id", "1"],
+    );
+
+    assert_eq!(
+        read_rows(&output.path().join("1-foo.csv")),
+        vec![
+            "timestamp,type,unit_price,quantity,listings",
+            "2018-10-01 00:00:00 UTC,buy,101,10,1",
+            "2018-10-02 00:00:00 UTC,sell,202,10,1",
+        ]
+    );
+}
+
+#[test]
+fn writes_items_sharing_a_name_to_separate_files() {
+    let server = MockServer::start();
+    server
+        .json("/v0.9/json/item/1", &item_result(1, "Foo"))
+        .json("/v0.9/json/item/2", &item_result(2, "Foo"));
+    serve_listings(&server, 1);
+    serve_listings(&server, 2);
+
+    let output = tempfile::tempdir().expect("temporary directory");
+    run(
+        &server,
+        output.path(),
+        &["listings", "-i", "1", "-i", "2", "-i", "1"],
+    );
+
+    assert!(output.path().join("1-foo.csv").exists());
+    assert!(output.path().join("2-foo.csv").exists());
+    assert_eq!(server.request_count("/v0.9/json/listings/1/sell/1"), 1);
+}
+
+#[test]
+fn names_files_with_the_filename_template() {
+    let server = MockServer::start();
+    server.json("/v0.9/json/item/1", &item_result(1, "Foo/Bar: Baz"));
+    serve_listings(&server, 1);
+
+    let output = tempfile::tempdir().expect("temporary directory");
+    run(
+        &server,
+        output.path(),
+        &[
+            "listings",
+            "-i",
+            "1",
+            "--filename-template",
+            "{name}_{id}.csv",
+        ],
+    );
+
+    assert_eq!(read_rows(&output.path().join("foo-bar-baz_1.csv")).len(), 5);
+    assert_eq!(csv_files(output.path()), 1);
+}
+
+#[test]
+fn rejects_unknown_filename_template_placeholders() {
+    let server = MockServer::start();
+
+    let output = tempfile::tempdir().expect("temporary directory");
+    let result = command(
+        &server,
+        output.path(),
+        &["listings", "-i", "1", "--filename-template", "{rarity}.csv"],
+    );
+
+    assert!(!result.status.success());
+    let stderr = String::from_utf8_lossy(&result.stderr);
+    assert!(
+        stderr.contains("unknown placeholder {rarity}"),
+        "stderr: {}",
+        stderr
+    );
+    assert!(server.requests().is_empty());
+}
+
+#[test]
+fn stops_fetching_listings_before_since() {
+    let server = MockServer::start();
+    server.json("/v0.9/json/item/1", &item_result(1, "Foo"));
+    serve_listings(&server, 1);
+
+    let output = tempfile::tempdir().expect("temporary directory");
+    run(
+        &server,
+        output.path(),
+        &["listings", "--item-id", "1", "--since", "2018-10-04"],
+    );
+
+    assert_eq!(
+        read_rows(&output.path().join("1-foo.csv")),
+        vec![
+            "timestamp,type,unit_price,quantity,listings",
+            "2018-10-04 00:00:00 UTC,buy,104,10,1",
+        ]
+    );
+    assert_eq!(server.request_count("/v0.9/json/listings/1/buy/2"), 0);
+}
+
+#[test]
+fn only_writes_listings_within_the_window() {
+    let server = MockServer::start();
+    server.json("/v0.9/json/item/1", &item_result(1, "Foo"));
+    serve_listings(&server, 1);
+
+    let output = tempfile::tempdir().expect("temporary directory");
+    run(
+        &server,
+        output.path(),
+        &[
+            "listings",
+            "--item-id",
+            "1",
+            "--since",
+            "2018-10-02T00:00:00Z",
+            "--until",
+            "2018-10-03T12:00:00Z",
+        ],
+    );
+
+    assert_eq!(
+        read_rows(&output.path().join("1-foo.csv")),
+        vec![
+            "timestamp,type,unit_price,quantity,listings",
+            "2018-10-02 00:00:00 UTC,sell,202,10,1",
+            "2018-10-03 00:00:00 UTC,buy,103,10,1",
+        ]
+    );
+}
+
+#[test]
+fn incremental_run_only_fetches_new_listings() {
+    let server = MockServer::start();
+    server.json("/v0.9/json/item/1", &item_result(1, "Foo"));
+    serve_listings(&server, 1);
+    // Served to the second run
+    server
+        .json(
+            "/v0.9/json/listings/1/buy/1",
+            &listings_page(
+                "buy",
+                1,
+                2,
+                vec![
+                    listing("2018-10-06 00:00:00 UTC", 106),
+                    listing("2018-10-05 00:00:00 UTC", 105),
+                    listing("2018-10-04 00:00:00 UTC", 104),
+                ],
+            ),
+        ).json(
+            "/v0.9/json/listings/1/sell/1",
+            &listings_page(
+                "sell",
+                1,
+                1,
+                vec![
+                    listing("2018-10-07 00:00:00 UTC", 207),
+                    listing("2018-10-02 00:00:00 UTC", 202),
+                ],
+            ),
+        );
+
+    let output = tempfile::tempdir().expect("temporary directory");
+    run(&server, output.path(), &["listings", "--item-id", "1"]);
+    run(
+        &server,
+        output.path(),
+        &["listings", "--incremental", "--item-id", "1"],
+    );
+
+    assert_eq!(
+        read_rows(&output.path().join("1-foo.csv")),
+        vec![
+            "timestamp,type,unit_price,quantity,listings",
+            "2018-10-01 00:00:00 UTC,buy,101,10,1",
+            "2018-10-02 00:00:00 UTC,sell,202,10,1",
+            "2018-10-03 00:00:00 UTC,buy,103,10,1",
+            "2018-10-04 00:00:00 UTC,buy,104,10,1",
+            "2018-10-05 00:00:00 UTC,buy,105,10,1",
+            "2018-10-06 00:00:00 UTC,buy,106,10,1",
+            "2018-10-07 00:00:00 UTC,sell,207,10,1",
+        ]
+    );
+    assert_eq!(server.request_count("/v0.9/json/listings/1/buy/1"), 2);
+    // The rest of the history has already been written
+    assert_eq!(server.request_count("/v0.9/json/listings/1/buy/2"), 1);
+}
+
+#[test]
+fn incremental_runs_keep_one_manifest_row_per_item() {
+    let server = MockServer::start();
+    server
+        .json("/v0.9/json/item/1", &item_result(1, "Foo"))
+        .json("/v0.9/json/item/2", &item_result(2, "Bar"));
+    serve_listings(&server, 1);
+    serve_listings(&server, 2);
+    // Served to the second run
+    server.json(
+        "/v0.9/json/listings/1/buy/1",
+        &listings_page(
+            "buy",
+            1,
+            2,
+            vec![
+                listing("2018-10-05 00:00:00 UTC", 105),
+                listing("2018-10-04 00:00:00 UTC", 104),
+            ],
+        ),
+    );
+
+    let output = tempfile::tempdir().expect("temporary directory");
+    for _ in 0..2 {
+        run(
+            &server,
+            output.path(),
+            &["listings", "--incremental", "-i", "1", "-i", "2"],
+        );
+    }
+
+    let rows = read_rows(&output.path().join("items.csv"));
+    assert_eq!(rows.len(), 3, "rows: {:?}", rows);
+    assert!(rows[0].starts_with("id,name,"), "header: {}", rows[0]);
+    // Counts include the listings written by the first run
+    assert!(rows[1].starts_with("1,Foo,"), "row: {}", rows[1]);
+    assert!(rows[1].contains(",1-foo.csv,4,1,"), "row: {}", rows[1]);
+    assert!(rows[2].starts_with("2,Bar,"), "row: {}", rows[2]);
+    assert!(rows[2].contains(",2-bar.csv,3,1,"), "row: {}", rows[2]);
+}
+
+#[test]
+fn writes_a_manifest_of_written_items() {
+    let server = MockServer::start();
+    server
+        .json("/v0.9/json/item/1", &item_result(1, "Foo"))
+        .json("/v0.9/json/item/2", &item_result(2, "Bar"));
+    serve_listings(&server, 1);
+
+    let output = tempfile::tempdir().expect("temporary directory");
+    run(&server, output.path(), &["listings", "-i", "1", "-i", "2"]);
+
+    let rows = read_rows(&output.path().join("items.csv"));
+    assert_eq!(rows.len(), 2);
+    assert!(
+        rows[0].starts_with("id,name,rarity,restriction_level,img,price_last_changed,"),
+        "header: {}",
+        rows[0]
+    );
+    assert!(
+        rows[0].ends_with(",output,buy_listings,sell_listings,fetched_at"),
+        "header: {}",
+        rows[0]
+    );
+    assert!(rows[1].starts_with("1,Foo,1,0,"), "row: {}", rows[1]);
+    assert!(rows[1].contains(",1-foo.csv,3,1,"), "row: {}", rows[1]);
+}
+
+#[test]
+fn keeps_manifest_entries_on_resume() {
+    let server = MockServer::start();
+    server
+        .json("/v0.9/json/item/1", &item_result(1, "Foo"))
+        .json("/v0.9/json/item/2", &item_result(2, "Bar"));
+    serve_listings(&server, 1);
+    serve_listings(&server, 2);
+
+    let output = tempfile::tempdir().expect("temporary directory");
+    run(
+        &server,
+        output.path(),
+        &[
+            "listings",
+            "-i",
+            "1",
+            "--format",
+            "jsonl",
+            "--manifest",
+            "json",
+        ],
+    );
+    run(
+        &server,
+        output.path(),
+        &[
+            "listings",
+            "-i",
+            "1",
+            "-i",
+            "2",
+            "--format",
+            "jsonl",
+            "--manifest",
+            "json",
+            "--resume",
+        ],
+    );
+
+    let manifest = fs::read_to_string(output.path().join("items.json")).unwrap();
+    let manifest: serde_json::Value = serde_json::from_str(&manifest).unwrap();
+    let entries = manifest.as_array().unwrap();
+    assert_eq!(entries.len(), 2);
+    assert_eq!(entries[0]["id"], json!(1));
+    assert_eq!(entries[1]["id"], json!(2));
+    assert_eq!(entries[1]["output"], json!("listings.jsonl"));
+    assert_eq!(entries[1]["buy_listings"], json!(3));
+    assert_eq!(read_rows(&output.path().join("listings.jsonl")).len(), 8);
+}
+
+#[test]
+fn keeps_json_manifest_entries_of_an_interrupted_run() {
+    let server = MockServer::start();
+    server
+        .json("/v0.9/json/item/1", &item_result(1, "Foo"))
+        .json("/v0.9/json/item/2", &item_result(2, "Bar"))
+        // Keeps the first run busy with item 2 until it is killed
+        .route(
+            "/v0.9/json/listings/2/buy/1",
+            Response::json(&listings_page("buy", 1, 1, vec![])).held_until(2),
+        );
+    serve_listings(&server, 1);
+    serve_listings(&server, 2);
+
+    let output = tempfile::tempdir().expect("temporary directory");
+    let args = ["listings", "-i", "1", "-i", "2", "--manifest", "json"];
+    let mut child = binary(&server, output.path(), &args)
+        .stderr(Stdio::null())
+        .spawn()
+        .expect("to run binary");
+    // Item 1 has been completed once item 2 is being fetched
+    let deadline = Instant::now() + Duration::from_secs(5);
+    while server.request_count("/v0.9/json/listings/2/buy/1") == 0 {
+        assert!(Instant::now() < deadline, "item 2 was not fetched");
+        thread::sleep(Duration::from_millis(20));
+    }
+    child.kill().unwrap();
+    child.wait().unwrap();
+    assert!(output.path().join("checkpoint.jsonl").exists());
+    assert!(!output.path().join("items.json").exists());
+
+    let mut resume = args.to_vec();
+    resume.push("--resume");
+    run(&server, output.path(), &resume);
+
+    let manifest = fs::read_to_string(output.path().join("items.json")).unwrap();
+    let manifest: serde_json::Value = serde_json::from_str(&manifest).unwrap();
+    let ids: Vec<_> = manifest
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|entry| entry["id"].clone())
+        .collect();
+    assert_eq!(ids, vec![json!(1), json!(2)]);
+    assert_eq!(server.request_count("/v0.9/json/listings/1/buy/1"), 1);
+    assert!(!output.path().join("items.partial.jsonl").exists());
+}
+
+#[test]
+fn writes_listings_of_every_item_to_json_lines() {
+    let server = MockServer::start();
+    server
+        .json("/v0.9/json/item/1", &item_result(1, "Foo"))
+        .json("/v0.9/json/item/2", &item_result(2, "Bar"));
+    serve_listings(&server, 1);
+    serve_listings(&server, 2);
+
+    let output = tempfile::tempdir().expect("temporary directory");
+    run(
+        &server,
+        output.path(),
+        &["listings", "--format", "jsonl", "-i", "1", "-i", "2"],
+    );
+
+    let rows: Vec<serde_json::Value> = read_rows(&output.path().join("listings.jsonl"))
+        .iter()
+        .map(|row| serde_json::from_str(row).unwrap())
+        .collect();
+    assert_eq!(rows.len(), 8);
+    assert_eq!(
+        rows[0],
+        json!({
+            "item_id": 1,
+            "item_name": "Foo",
+            "timestamp": "2018-10-01 00:00:00 UTC",
+            "type": "buy",
+            "unit_price": 101,
+            "quantity": 10,
+            "listings": 1,
+        })
+    );
+    assert_eq!(rows[4]["item_id"], json!(2));
+    assert_eq!(csv_files(output.path()), 0);
+}
+
+#[test]
+fn resumes_json_lines_after_an_incomplete_line() {
+    let server = MockServer::start();
+    server
+        .json("/v0.9/json/item/1", &item_result(1, "Foo"))
+        .json("/v0.9/json/item/2", &item_result(2, "Bar"));
+    serve_listings(&server, 1);
+    serve_listings(&server, 2);
+
+    let output = tempfile::tempdir().expect("temporary directory");
+    run(
+        &server,
+        output.path(),
+        &["listings", "--format", "jsonl", "-i", "1"],
+    );
+    // Left behind by a run that died while writing item 2
+    let mut listings = fs::OpenOptions::new()
+        .append(true)
+        .open(output.path().join("listings.jsonl"))
+        .unwrap();
+    write!(listings, "{{\"item_id\":2,\"item_na").unwrap();
+    run(
+        &server,
+        output.path(),
+        &[
+            "listings", "--format", "jsonl", "-i", "1", "-i", "2", "--resume",
+        ],
+    );
+
+    let rows: Vec<serde_json::Value> = read_rows(&output.path().join("listings.jsonl"))
+        .iter()
+        .map(|row| serde_json::from_str(row).unwrap())
+        .collect();
+    assert_eq!(rows.len(), 8);
+    assert_eq!(rows[4]["item_id"], json!(2));
+}
+
+#[test]
+fn resume_replaces_json_lines_of_items_that_were_not_completed() {
+    let server = MockServer::start();
+    server
+        .json("/v0.9/json/item/1", &item_result(1, "Foo"))
+        .json("/v0.9/json/item/2", &item_result(2, "Bar"));
+    serve_listings(&server, 1);
+    serve_listings(&server, 2);
+
+    let output = tempfile::tempdir().expect("temporary directory");
+    let args = ["listings", "--format", "jsonl", "-i", "1", "-i", "2"];
+    run(&server, output.path(), &args);
+    // As if the run died after writing item 2, before it was checkpointed
+    fs::write(
+        output.path().join("checkpoint.jsonl"),
+        format!("{}\n", json!({ "type": "item", "id": 1 })),
+    ).unwrap();
+    let mut resume = args.to_vec();
+    resume.push("--resume");
+    run(&server, output.path(), &resume);
+
+    let rows = read_rows(&output.path().join("listings.jsonl"));
+    let mut unique = rows.clone();
+    unique.sort();
+    unique.dedup();
+    assert_eq!(rows.len(), 8);
+    assert_eq!(unique.len(), 8);
+    assert_eq!(server.request_count("/v0.9/json/listings/2/sell/1"), 2);
+}
+
+#[cfg(feature = "sqlite")]
+#[test]
+fn writes_items_and_listings_to_sqlite() {
+    let server = MockServer::start();
+    server.json("/v0.9/json/item/1", &item_result(1, "Foo"));
+    serve_listings(&server, 1);
+
+    let output = tempfile::tempdir().expect("temporary directory");
+    // Running twice replaces the listings of the item
+    for _ in 0..2 {
+        run(
+            &server,
+            output.path(),
+            &["listings", "--format", "sqlite", "--item-id", "1"],
+        );
+    }
+
+    let connection = rusqlite::Connection::open(output.path().join("listings.sqlite")).unwrap();
+    let name: String = connection
+        .query_row(
+            "SELECT name FROM items WHERE id = 1",
+            rusqlite::NO_PARAMS,
+            |row| row.get(0),
+        ).unwrap();
+    let mut statement = connection
+        .prepare("SELECT timestamp, type, unit_price FROM listings WHERE item_id = 1")
+        .unwrap();
+    let listings: Vec<(String, String, i64)> = statement
+        .query_map(rusqlite::NO_PARAMS, |row| {
+            Ok((row.get(0)?, row.get(1)?, row.get(2)?))
+        }).unwrap()
+        .map(Result::unwrap)
+        .collect();
+
+    assert_eq!(name, "Foo");
+    assert_eq!(listings.len(), 4);
+    assert_eq!(
+        listings[0],
+        ("2018-10-01 00:00:00".to_string(), "buy".to_string(), 101)
+    );
+}
+
+#[cfg(feature = "sqlite")]
+#[test]
+fn replaces_the_sqlite_database_unless_resuming() {
+    let server = MockServer::start();
+    server
+        .json("/v0.9/json/item/1", &item_result(1, "Foo"))
+        .json("/v0.9/json/item/2", &item_result(2, "Bar"));
+    serve_listings(&server, 1);
+    serve_listings(&server, 2);
+
+    let output = tempfile::tempdir().expect("temporary directory");
+    let item_ids = || {
+        let connection = rusqlite::Connection::open(output.path().join("listings.sqlite")).unwrap();
+        let mut statement = connection
+            .prepare("SELECT DISTINCT item_id FROM listings ORDER BY item_id")
+            .unwrap();
+        let ids: Vec<i64> = statement
+            .query_map(rusqlite::NO_PARAMS, |row| row.get(0))
+            .unwrap()
+            .map(Result::unwrap)
+            .collect();
+        let items: i64 = connection
+            .query_row("SELECT COUNT(*) FROM items", rusqlite::NO_PARAMS, |row| {
+                row.get(0)
+            }).unwrap();
+        (ids, items)
+    };
+
+    run(
+        &server,
+        output.path(),
+        &["listings", "--format", "sqlite", "-i", "1"],
+    );
+    run(
+        &server,
+        output.path(),
+        &["listings", "--format", "sqlite", "-i", "2"],
+    );
+    assert_eq!(item_ids(), (vec![2], 1));
+
+    run(
+        &server,
+        output.path(),
+        &["listings", "--format", "sqlite", "--incremental", "-i", "1"],
+    );
+    assert_eq!(item_ids(), (vec![1, 2], 2));
+}
+
+#[cfg(feature = "parquet")]
+#[test]
+fn writes_listings_of_every_item_to_parquet() {
+    use parquet::file::reader::{FileReader, SerializedFileReader};
+
+    let server = MockServer::start();
+    server
+        .json("/v0.9/json/item/1", &item_result(1, "Foo"))
+        .json("/v0.9/json/item/2", &item_result(2, "Bar"));
+    serve_listings(&server, 1);
+    serve_listings(&server, 2);
+
+    let output = tempfile::tempdir().expect("temporary directory");
+    run(
+        &server,
+        output.path(),
+        &["listings", "--format", "parquet", "-i", "1", "-i", "2"],
+    );
+
+    let file = fs::File::open(output.path().join("listings.parquet")).unwrap();
+    let reader = SerializedFileReader::new(file).unwrap();
+    let rows: Vec<_> = reader
+        .get_row_iter(None)
+        .unwrap()
+        .map(|row| row.unwrap().to_string())
+        .collect();
+
+    assert_eq!(reader.metadata().file_metadata().num_rows(), 8);
+    assert_eq!(
+        rows[0],
+        "{item_id: 1, item_name: \"Foo\", timestamp: 2018-10-01 00:00:00 +00:00, type: \"buy\", \
+         unit_price: 101, quantity: 10, listings: 1}"
+    );
+}
+
+#[test]
+fn resolves_item_type_names() {
+    let server = MockServer::start();
+    let mut greatsword = item(1, "Foo");
+    greatsword["type_id"] = json!(18);
+    greatsword["sub_type_id"] = json!(6);
+    server
+        .json("/v0.9/json/item/1", &json!({ "result": greatsword }))
+        .json("/v0.9/json/types", &types());
+    serve_listings(&server, 1);
+
+    let output = tempfile::tempdir().expect("temporary directory");
+    let result = run(
+        &server,
+        output.path(),
+        &["listings", "--type-names", "--item-id", "1"],
+    );
+
+    let stderr = String::from_utf8_lossy(&result.stderr);
+    assert!(
+        stderr.contains("\"Foo\" (Weapon / Greatsword)"),
+        "stderr: {}",
+        stderr
+    );
+    let rows = read_rows(&output.path().join("items.csv"));
+    assert!(
+        rows[0].contains(",type_id,sub_type_id,type_name,sub_type_name,"),
+        "header: {}",
+        rows[0]
+    );
+    assert!(
+        rows[1].contains(",18,6,Weapon,Greatsword,"),
+        "row: {}",
+        rows[1]
+    );
+}
+
+#[test]
+fn fetches_listings_for_search_results_across_pages() {
+    let server = MockServer::start();
+    server
+        .json(
+            "/v0.9/json/item-search/Foo/1",
+            &items_page(1, 2, vec![item(1, "Foo")]),
+        ).json(
+            "/v0.9/json/item-search/Foo/2",
+            &items_page(2, 2, vec![item(2, "Foo Bar")]),
+        );
+    serve_listings(&server, 1);
+    serve_listings(&server, 2);
+
+    let output = tempfile::tempdir().expect("temporary directory");
+    run(&server, output.path(), &["listings", "--item-name", "Foo"]);
+
+    assert_eq!(read_rows(&output.path().join("1-foo.csv")).len(), 5);
+    assert_eq!(read_rows(&output.path().join("2-foo-bar.csv")).len(), 5);
+    assert_eq!(server.request_count("/v0.9/json/item-search/Foo/1"), 1);
+    assert_eq!(server.request_count("/v0.9/json/item-search/Foo/2"), 1);
+}
+
+#[test]
+fn fetches_listings_for_all_items() {
+    let server = MockServer::start();
+    server.json(
+        "/v0.9/json/items/all/1",
+        &items_page(1, 1, vec![item(1, "Foo"), item(2, "Bar")]),
+    );
+    serve_listings(&server, 1);
+    serve_listings(&server, 2);
+
+    let output = tempfile::tempdir().expect("temporary directory");
+    run(&server, output.path(), &["listings", "--all"]);
+
+    assert!(output.path().join("1-foo.csv").exists());
+    assert!(output.path().join("2-bar.csv").exists());
+}
+
+#[test]
+fn prints_items_by_id() {
+    let server = MockServer::start();
+    server
+        .json("/v0.9/json/item/1", &item_result(1, "Foo"))
+        .json("/v0.9/json/item/2", &item_result(2, "Bar"));
+
+    let output = tempfile::tempdir().expect("temporary directory");
+    let result = run(&server, output.path(), &["item", "1", "2"]);
+
+    let stdout = String::from_utf8_lossy(&result.stdout);
+    let rows: Vec<&str> = stdout.lines().collect();
+    assert_eq!(rows.len(), 3);
+    assert!(rows[0].starts_with("data_id,name,"), "stdout: {}", stdout);
+    assert!(rows[1].starts_with("1,Foo,"), "stdout: {}", stdout);
+    assert!(rows[2].starts_with("2,Bar,"), "stdout: {}", stdout);
+}
+
+#[test]
+fn prints_item_types() {
+    let server = MockServer::start();
+    server.json("/v0.9/json/types", &types());
+
+    let output = tempfile::tempdir().expect("temporary directory");
+    let result = run(&server, output.path(), &["types"]);
+
+    assert_eq!(
+        String::from_utf8_lossy(&result.stdout)
+            .lines()
+            .collect::<Vec<_>>(),
+        vec![
+            "type_id,type_name,sub_type_id,sub_type_name",
+            "5,Consumable,,",
+            "18,Weapon,,",
+            "18,Weapon,0,Sword",
+            "18,Weapon,6,Greatsword",
+        ]
+    );
+    assert_eq!(csv_files(output.path()), 0);
+}
+
+#[test]
+fn prints_search_results_as_a_table() {
+    let server = MockServer::start();
+    server.json(
+        "/v0.9/json/item-search/Foo/1",
+        &items_page(1, 1, vec![item(1, "Foo"), item(12, "Foo Bar")]),
+    );
+
+    let output = tempfile::tempdir().expect("temporary directory");
+    let result = run(&server, output.path(), &["search", "Foo"]);
+
+    assert_eq!(
+        String::from_utf8_lossy(&result.stdout)
+            .lines()
+            .collect::<Vec<_>>(),
+        vec![
+            "ID  NAME     RARITY  LEVEL  MAX OFFER  MIN SALE",
+            "1   Foo      Common  0      100        120",
+            "12  Foo Bar  Common  0      100        120",
+        ]
+    );
+    assert_eq!(server.request_count("/v0.9/json/listings/1/buy/1"), 0);
+}
+
+#[test]
+fn prints_search_results_as_json() {
+    let server = MockServer::start();
+    server.json(
+        "/v0.9/json/item-search/Foo/1",
+        &items_page(1, 1, vec![item(1, "Foo")]),
+    );
+
+    let output = tempfile::tempdir().expect("temporary directory");
+    let result = run(
+        &server,
+        output.path(),
+        &["search", "Foo", "--format", "json"],
+    );
+
+    let printed: serde_json::Value = serde_json::from_slice(&result.stdout).unwrap();
+    assert_eq!(
+        printed,
+        json!([{
+            "id": 1,
+            "name": "Foo",
+            "rarity": "Common",
+            "level": 0,
+            "max_offer_unit_price": 100,
+            "min_sale_unit_price": 120,
+        }])
+    );
+}
+
+#[test]
+fn dry_run_prints_items_without_fetching_listings() {
+    let server = MockServer::start();
+    server
+        .json(
+            "/v0.9/json/item-search/Foo/1",
+            &items_page(1, 1, vec![item(1, "Foo")]),
+        ).json("/v0.9/json/item/2", &item_result(2, "Bar"));
+
+    let output = tempfile::tempdir().expect("temporary directory");
+    let result = run(
+        &server,
+        output.path(),
+        &[
+            "listings",
+            "--dry-run",
+            "--item-name",
+            "Foo",
+            "--item-id",
+            "2",
+        ],
+    );
+
+    let stdout = String::from_utf8_lossy(&result.stdout);
+    let rows: Vec<&str> = stdout.lines().collect();
+    assert_eq!(rows.len(), 3, "stdout: {}", stdout);
+    assert!(rows[1].starts_with("1   Foo"), "stdout: {}", stdout);
+    assert!(rows[2].starts_with("2   Bar"), "stdout: {}", stdout);
+    assert_eq!(csv_files(output.path()), 0);
+    assert!(!output.path().join("checkpoint.jsonl").exists());
+    assert_eq!(
+        server.requests(),
+        vec!["/v0.9/json/item-search/Foo/1", "/v0.9/json/item/2"]
+    );
+}
+
+#[test]
+fn accepts_global_options_before_the_subcommand() {
+    let server = MockServer::start();
+    server.json("/v0.9/json/item/1", &item_result(1, "Foo"));
+    serve_listings(&server, 1);
+
+    let output = tempfile::tempdir().expect("temporary directory");
+    let result = Command::new(env!("CARGO_BIN_EXE_spidy-scrapey"))
+        .arg("--base-url")
+        .arg(server.base_url())
+        .arg("-o")
+        .arg(output.path())
+        .args(["--max-backoff", "0", "listings", "--item-id", "1"])
+        .output()
+        .expect("to run binary");
+
+    assert!(result.status.success());
+    assert!(output.path().join("1-foo.csv").exists());
+}
+
+#[test]
+fn fetches_listings_for_items_of_a_sub_type() {
+    let server = MockServer::start();
+    let mut greatsword = item(1, "Foo");
+    greatsword["type_id"] = json!(18);
+    greatsword["sub_type_id"] = json!(6);
+    let mut sword = item(2, "Bar");
+    sword["type_id"] = json!(18);
+    server.json("/v0.9/json/types", &types()).json(
+        "/v0.9/json/items/18/1",
+        &items_page(1, 1, vec![greatsword, sword]),
+    );
+    serve_listings(&server, 1);
+
+    let output = tempfile::tempdir().expect("temporary directory");
+    run(
+        &server,
+        output.path(),
+        &["listings", "--type", "weapon", "--sub-type", "Greatsword"],
+    );
+
+    assert!(output.path().join("1-foo.csv").exists());
+    assert_eq!(csv_files(output.path()), 1);
+    assert_eq!(server.request_count("/v0.9/json/listings/2/buy/1"), 0);
+}
+
+#[test]
+fn does_not_fetch_types_for_type_ids() {
+    let server = MockServer::start();
+    server.json(
+        "/v0.9/json/items/5/1",
+        &items_page(1, 1, vec![item(1, "Foo")]),
+    );
+    serve_listings(&server, 1);
+
+    let output = tempfile::tempdir().expect("temporary directory");
+    run(
+        &server,
+        output.path(),
+        &["listings", "--type", "5", "--sub-type", "0"],
+    );
+
+    assert!(output.path().join("1-foo.csv").exists());
+    assert_eq!(server.request_count("/v0.9/json/types"), 0);
+}
+
+#[test]
+fn fails_on_unknown_type_name() {
+    let server = MockServer::start();
+    server.json("/v0.9/json/types", &types());
+
+    let output = tempfile::tempdir().expect("temporary directory");
+    let result = command(&server, output.path(), &["listings", "--type", "Nope"]);
+
+    assert!(!result.status.success());
+    let stderr = String::from_utf8_lossy(&result.stderr);
+    assert!(
+        stderr.contains("Unknown item type \"Nope\""),
+        "stderr: {}",
+        stderr
+    );
+}
+
+#[test]
+fn dumps_recipes() {
+    let server = MockServer::start();
+    server
+        .json(
+            "/v0.9/json/disciplines",
+            &json!({ "results": [{ "id": 1, "name": "Huntsman" }] }),
+        ).json(
+            "/v0.9/json/recipes/1/1",
+            &items_page(1, 1, vec![recipe(1, "Foo"), recipe(2, "Bar")]),
+        ).json(
+            "/v0.9/json/recipe/1",
+            &recipe_result(1, "Foo", &[(10, 2), (11, 1)]),
+        );
+
+    let output = tempfile::tempdir().expect("temporary directory");
+    run(
+        &server,
+        output.path(),
+        &["recipes", "--discipline", "huntsman", "--ingredients"],
+    );
+
+    assert_eq!(
+        read_rows(&output.path().join("recipes.csv")),
+        vec![
+            "id,name,discipline_id,result_item_id,result_count,crafting_cost,\
+             min_sale_unit_price,max_offer_unit_price,rating,ingredients",
+            "1,Foo,1,1001,5,80,110,90,25,10:2;11:1",
+            "2,Bar,1,1002,5,80,110,90,25,",
+        ]
+    );
+}
+
+#[test]
+fn ranks_profitable_recipes() {
+    let mut ingredient = item(10, "Ingredient");
+    ingredient["max_offer_unit_price"] = json!(10);
+    ingredient["min_sale_unit_price"] = json!(12);
+    let mut cheap = item(1001, "Cheap");
+    cheap["max_offer_unit_price"] = json!(100);
+    let mut expensive = item(1002, "Expensive");
+    expensive["max_offer_unit_price"] = json!(100);
+
+    let server = MockServer::start();
+    server
+        .json(
+            "/v0.9/json/recipes/all/1",
+            &items_page(1, 1, vec![recipe(1, "Foo"), recipe(2, "Bar")]),
+        ).json("/v0.9/json/recipe/1", &recipe_result(1, "Foo", &[(10, 1)]))
+        .json("/v0.9/json/recipe/2", &recipe_result(2, "Bar", &[(10, 50)]))
+        .json(
+            "/v0.9/json/items/all/1",
+            &items_page(1, 1, vec![ingredient, cheap, expensive]),
+        );
+
+    let output = tempfile::tempdir().expect("temporary directory");
+    run(&server, output.path(), &["profit", "--buy", "order"]);
+
+    // 5 results sold for 100 each, minus the 15% fee, and one ingredient bought for 10
+    assert_eq!(
+        read_rows(&output.path().join("profit.csv")),
+        vec![
+            "rank,recipe_id,name,result_item_id,result_count,cost,revenue,profit,crafted",
+            "1,1,Foo,1001,5,10,425,415,",
+        ]
+    );
+}
+
+#[test]
+fn appends_polled_gem_prices() {
+    let server = MockServer::start();
+    server
+        .route("/v0.9/json/gem-price", Response::status(404))
+        .json(
+            "/v0.9/json/gem-price",
+            &json!({ "result": { "gem_to_gold": 1500, "gold_to_gem": 2500 } }),
+        );
+
+    let output = tempfile::tempdir().expect("temporary directory");
+    run(
+        &server,
+        output.path(),
+        &["gems", "--interval", "0", "--count", "2"],
+    );
+    run(&server, output.path(), &["gems"]);
+
+    // The first poll failed and is skipped
+    let rows = read_rows(&output.path().join("gems.csv"));
+    assert_eq!(rows.len(), 3);
+    assert_eq!(rows[0], "timestamp,gem_to_gold,gold_to_gem");
+    assert!(rows[1].ends_with(" UTC,1500,2500"), "row: {}", rows[1]);
+    assert!(rows[2].ends_with(" UTC,1500,2500"), "row: {}", rows[2]);
+}
+
+#[test]
+fn aggregates_listings_into_weekly_buckets() {
+    let server = MockServer::start();
+    server.json("/v0.9/json/item/1", &item_result(1, "Foo"));
+    serve_listings(&server, 1);
+
+    let output = tempfile::tempdir().expect("temporary directory");
+    run(&server, output.path(), &["listings", "--item-id", "1"]);
+    let listings = output.path().join("1-foo.csv");
+    run(
+        &server,
+        output.path(),
+        &[
+            "aggregate",
+            "--interval",
+            "week",
+            listings.to_str().unwrap(),
+        ],
+    );
+
+    assert_eq!(
+        read_rows(&output.path().join("1-foo.week.csv")),
+        vec![
+            "timestamp,type,open,high,low,close,min_quantity,max_quantity,avg_quantity,\
+             min_listings,max_listings,avg_listings,samples",
+            "2018-10-01 00:00:00 UTC,buy,101,104,101,104,10,10,10.0,1,1,1.0,3",
+            "2018-10-01 00:00:00 UTC,sell,202,202,202,202,10,10,10.0,1,1,1.0,1",
+        ]
+    );
+}
+
+#[test]
+fn joins_buy_and_sell_listings_with_their_spread() {
+    let server = MockServer::start();
+    server.json("/v0.9/json/item/1", &item_result(1, "Foo"));
+    serve_listings(&server, 1);
+
+    let output = tempfile::tempdir().expect("temporary directory");
+    run(&server, output.path(), &["listings", "--item-id", "1"]);
+    let listings = output.path().join("1-foo.csv");
+    run(
+        &server,
+        output.path(),
+        &["spread", listings.to_str().unwrap()],
+    );
+
+    assert_eq!(
+        read_rows(&output.path().join("1-foo.spread.csv")),
+        vec![
+            "timestamp,buy_price,buy_quantity,sell_price,sell_quantity,spread,spread_after_fee",
+            "2018-10-01 00:00:00 UTC,101,10,,,,",
+            "2018-10-02 00:00:00 UTC,101,10,202,10,101,70",
+            "2018-10-03 00:00:00 UTC,103,10,202,10,99,68",
+            "2018-10-04 00:00:00 UTC,104,10,202,10,98,67",
+        ]
+    );
+}
+
+#[test]
+fn skips_items_that_cannot_be_fetched() {
+    let server = MockServer::start();
+    serve_listings(&server, 1);
+
+    let output = tempfile::tempdir().expect("temporary directory");
+    run(&server, output.path(), &["listings", "--item-id", "1"]);
+
+    assert_eq!(server.requests(), vec!["/v0.9/json/item/1"]);
+    assert_eq!(csv_files(output.path()), 0);
+}
+
+#[test]
+fn skips_items_with_unknown_rarity() {
+    let server = MockServer::start();
+    let mut unknown = item(1, "Foo");
+    unknown["rarity"] = json!(42);
+    server.json("/v0.9/json/item/1", &json!({ "result": unknown }));
+
+    let output = tempfile::tempdir().expect("temporary directory");
+    let result = run(&server, output.path(), &["listings", "--item-id", "1"]);
+
+    let stderr = String::from_utf8_lossy(&result.stderr);
+    assert!(stderr.contains("Unknown rarity"), "stderr: {}", stderr);
+    assert!(stderr.contains("/v0.9/json/item/1"), "stderr: {}", stderr);
+}
+
+#[test]
+fn aborts_when_a_page_of_items_cannot_be_fetched() {
+    let server = MockServer::start();
+    server.json(
+        "/v0.9/json/items/all/1",
+        &items_page(1, 2, vec![item(1, "Foo")]),
+    );
+    server.route("/v0.9/json/items/all/2", Response::status(404));
+    serve_listings(&server, 1);
+
+    let output = tempfile::tempdir().expect("temporary directory");
+    let result = command(&server, output.path(), &["listings", "--all"]);
+
+    assert!(!result.status.success());
+    let stderr = String::from_utf8_lossy(&result.stderr);
+    assert!(stderr.contains("HTTP 404"), "stderr: {}", stderr);
+    assert!(stderr.contains("(page 2)"), "stderr: {}", stderr);
+    assert!(output.path().join("1-foo.csv").exists());
+}
+
+#[test]
+fn retries_transient_failures() {
+    let server = MockServer::start();
+    server
+        .route("/v0.9/json/item/1", Response::status(503))
+        .json("/v0.9/json/item/1", &item_result(1, "Foo"))
+        .route(
+            "/v0.9/json/listings/1/sell/1",
+            Response::status(429).header("Retry-After", "1"),
+        );
+    serve_listings(&server, 1);
+
+    let output = tempfile::tempdir().expect("temporary directory");
+    run(&server, output.path(), &["listings", "--item-id", "1"]);
+
+    assert_eq!(read_rows(&output.path().join("1-foo.csv")).len(), 5);
+    assert_eq!(server.request_count("/v0.9/json/item/1"), 2);
+    assert_eq!(server.request_count("/v0.9/json/listings/1/sell/1"), 2);
+}
+
+#[test]
+fn gives_up_after_max_attempts() {
+    let server = MockServer::start();
+    server.route("/v0.9/json/item/1", Response::status(502));
+
+    let output = tempfile::tempdir().expect("temporary directory");
+    run(
+        &server,
+        output.path(),
+        &["listings", "--item-id", "1", "--max-attempts", "2"],
+    );
+
+    assert_eq!(server.request_count("/v0.9/json/item/1"), 2);
+    assert_eq!(csv_files(output.path()), 0);
+}
+
+#[test]
+fn resume_skips_completed_items() {
+    let server = MockServer::start();
+    server.json("/v0.9/json/item/1", &item_result(1, "Foo"));
+    server.json("/v0.9/json/item/2", &item_result(2, "Bar"));
+    serve_listings(&server, 1);
+    serve_listings(&server, 2);
+
+    let output = tempfile::tempdir().expect("temporary directory");
+    run(&server, output.path(), &["listings", "--item-id", "1"]);
+    assert_eq!(server.requests().len(), 4);
+
+    run(
+        &server,
+        output.path(),
+        &["listings", "--item-id", "1", "--item-id", "2", "--resume"],
+    );
+    assert_eq!(server.request_count("/v0.9/json/item/1"), 1);
+    assert_eq!(server.request_count("/v0.9/json/listings/1/buy/1"), 1);
+    assert_eq!(server.request_count("/v0.9/json/item/2"), 1);
+    assert_eq!(server.request_count("/v0.9/json/listings/2/buy/1"), 1);
+    assert_eq!(csv_files(output.path()), 2);
+}
+
+#[test]
+fn resume_continues_mid_pagination() {
+    let server = MockServer::start();
+    server
+        .json(
+            "/v0.9/json/items/all/1",
+            &items_page(1, 2, vec![item(1, "Foo")]),
+        ).json(
+            "/v0.9/json/items/all/2",
+            &items_page(2, 2, vec![item(2, "Bar"), item(3, "Baz")]),
+        );
+    serve_listings(&server, 3);
+
+    let output = tempfile::tempdir().expect("temporary directory");
+    let mut checkpoint =
+        fs::File::create(output.path().join("checkpoint.jsonl")).expect("checkpoint file");
+    writeln!(
+        checkpoint,
+        "{}",
+        json!({
+            "type": "page",
+            "url": format!("{}/v0.9/json/items/all", server.base_url()),
+            "page": 2
+        })
+    ).unwrap();
+    writeln!(checkpoint, "{}", json!({ "type": "item", "id": 1 })).unwrap();
+    writeln!(checkpoint, "{}", json!({ "type": "item", "id": 2 })).unwrap();
+    // Truncated line from a crash
+    write!(checkpoint, "{{\"type\":\"ite").unwrap();
+
+    run(&server, output.path(), &["listings", "--all", "--resume"]);
+
+    assert_eq!(
+        server.requests(),
+        vec![
+            "/v0.9/json/items/all/2",
+            "/v0.9/json/listings/3/buy/1",
+            "/v0.9/json/listings/3/buy/2",
+            "/v0.9/json/listings/3/sell/1",
+        ]
+    );
+    assert!(output.path().join("3-baz.csv").exists());
+
+    let checkpoint = fs::read_to_string(output.path().join("checkpoint.jsonl")).unwrap();
+    assert!(checkpoint.ends_with("{\"type\":\"item\",\"id\":3}\n"));
+}
+
+#[test]
+fn resume_fetches_items_that_failed_on_an_earlier_page() {
+    let server = MockServer::start();
+    server
+        .json(
+            "/v0.9/json/items/all/1",
+            &items_page(1, 2, vec![item(1, "Foo")]),
+        ).json(
+            "/v0.9/json/items/all/2",
+            &items_page(2, 2, vec![item(2, "Bar")]),
+        ).route("/v0.9/json/listings/1/buy/1", Response::status(404));
+    serve_listings(&server, 1);
+    serve_listings(&server, 2);
+
+    let output = tempfile::tempdir().expect("temporary directory");
+    let result = run(&server, output.path(), &["listings", "--all"]);
+    assert!(String::from_utf8_lossy(&result.stderr).contains("\"Foo\" (ID 1)"));
+    assert!(!output.path().join("1-foo.csv").exists());
+
+    run(&server, output.path(), &["listings", "--all", "--resume"]);
+
+    assert!(output.path().join("1-foo.csv").exists());
+    assert_eq!(server.request_count("/v0.9/json/items/all/1"), 2);
+    assert_eq!(server.request_count("/v0.9/json/listings/2/sell/1"), 1);
+}
+
+/// The delays themselves are tested against a fake clock in `pacing`
+#[test]
+fn paces_and_rate_limits_requests() {
+    let server = MockServer::start();
+    for page in 1..4 {
+        server.json(
+            &format!("/v0.9/json/item-search/Foo/{}", page),
+            &items_page(page, 3, vec![]),
+        );
+    }
+
+    let output = tempfile::tempdir().expect("temporary directory");
+    run(
+        &server,
+        output.path(),
+        &[
+            "listings",
+            "--item-name",
+            "Foo",
+            "--pacing",
+            "fixed",
+            "--pacing-rate",
+            "1000",
+            "--rate-limit",
+            "1000/1",
+        ],
+    );
+
+    assert_eq!(server.requests().len(), 3);
+}
+
+#[test]
+fn fetches_items_concurrently() {
+    let server = MockServer::start();
+    server.json(
+        "/v0.9/json/items/all/1",
+        &items_page(
+            1,
+            1,
+            vec![
+                item(1, "Foo"),
+                item(2, "Bar"),
+                item(3, "Baz"),
+                item(4, "Qux"),
+            ],
+        ),
+    );
+    for id in 1..4 {
+        // Only answered once every job is waiting for its listings
+        server.route(
+            &format!("/v0.9/json/listings/{}/buy/1", id),
+            Response::json(&listings_page("buy", 1, 1, vec![])).held_until(3),
+        );
+        serve_listings(&server, id);
+    }
+
+    let output = tempfile::tempdir().expect("temporary directory");
+    let result = run(
+        &server,
+        output.path(),
+        &["listings", "--all", "--jobs", "3"],
+    );
+
+    assert_eq!(server.peak_held(), 3);
+    assert_eq!(csv_files(output.path()), 3);
+    let stderr = String::from_utf8_lossy(&result.stderr);
+    assert!(
+        stderr.contains("Unable to fetch listings for 1 items"),
+        "stderr: {}",
+        stderr
+    );
+    assert!(stderr.contains("\"Qux\" (ID 4)"), "stderr: {}", stderr);
+    for counter in 1..5 {
+        assert!(stderr.contains(&format!("[{} of ", counter)));
+    }
+}
@@ -0,0 +1,367 @@
+//! Local stand-in for the GW2Spidy API serving canned responses
+#![allow(dead_code)]
+
+use serde_json::Value;
+use std::collections::HashMap;
+use std::io::{BufRead, BufReader, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread;
+use std::time::Duration;
+
+pub const TIMESTAMP: &str = "2018-10-01 12:00:00 UTC";
+
+/// Canned response
+#[derive(Clone, Debug)]
+pub struct Response {
+    pub status: u16,
+    pub headers: Vec<(String, String)>,
+    pub body: String,
+    /// Number of held responses that must be waiting at once before any of them is sent
+    pub held_until: Option<usize>,
+}
+
+impl Response {
+    pub fn json(body: &Value) -> Self {
+        Self {
+            status: 200,
+            headers: vec![("Content-Type".to_string(), "application/json".to_string())],
+            body: body.to_string(),
+            held_until: None,
+        }
+    }
+
+    pub fn csv(rows: &[Value]) -> Self {
+        Self {
+            status: 200,
+            headers: vec![("Content-Type".to_string(), "text/csv".to_string())],
+            body: csv(rows),
+            held_until: None,
+        }
+    }
+
+    pub fn status(status: u16) -> Self {
+        Self {
+            status,
+            headers: vec![],
+            body: String::new(),
+            held_until: None,
+        }
+    }
+
+    pub fn header(mut self, name: &str, value: &str) -> Self {
+        self.headers.push((name.to_string(), value.to_string()));
+        self
+    }
+
+    /// Hold the response until `requests` held responses are waiting at once, so that a test
+    /// can tell whether requests are made concurrently. Gives up after `HOLD_TIMEOUT`.
+    pub fn held_until(mut self, requests: usize) -> Self {
+        self.held_until = Some(requests);
+        self
+    }
+}
+
+const HOLD_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Responses being held
+#[derive(Default)]
+struct Held {
+    waiting: usize,
+    peak: usize,
+    released: bool,
+}
+
+/// A mock server listening on a random local port
+///
+/// Each route is a queue of responses; the last response of a route is repeated once the queue
+/// has been drained.
+pub struct MockServer {
+    address: String,
+    routes: Arc<Mutex<HashMap<String, Vec<Response>>>>,
+    requests: Arc<Mutex<Vec<String>>>,
+    held: Arc<(Mutex<Held>, Condvar)>,
+}
+
+impl MockServer {
+    pub fn start() -> Self {
+        let listener = TcpListener::bind("127.0.0.1:0").expect("to bind to a local port");
+        let address = format!("http://{}", listener.local_addr().expect("local address"));
+        let routes: Arc<Mutex<HashMap<String, Vec<Response>>>> = Default::default();
+        let requests: Arc<Mutex<Vec<String>>> = Default::default();
+        let held: Arc<(Mutex<Held>, Condvar)> = Default::default();
+
+        let server_routes = Arc::clone(&routes);
+        let server_requests = Arc::clone(&requests);
+        let server_held = Arc::clone(&held);
+        thread::spawn(move || {
+            for stream in listener.incoming() {
+                let stream = match stream {
+                    Ok(stream) => stream,
+                    Err(_) => continue,
+                };
+                let routes = Arc::clone(&server_routes);
+                let requests = Arc::clone(&server_requests);
+                let held = Arc::clone(&server_held);
+                thread::spawn(move || handle(stream, &routes, &requests, &held));
+            }
+        });
+
+        Self {
+            address,
+            routes,
+            requests,
+            held,
+        }
+    }
+
+    /// Base URL to pass to the API client
+    pub fn base_url(&self) -> String {
+        format!("{}/api", self.address)
+    }
+
+    /// Queue a response for the given path, relative to the base URL (e.g. `/v0.9/json/item/1`)
+    pub fn route(&self, path: &str, response: Response) -> &Self {
+        let path = format!("/api{}", path);
+        self.routes
+            .lock()
+            .expect("not to be poisoned")
+            .entry(path)
+            .or_default()
+            .push(response);
+        self
+    }
+
+    pub fn json(&self, path: &str, body: &Value) -> &Self {
+        self.route(path, Response::json(body))
+    }
+
+    pub fn csv(&self, path: &str, rows: &[Value]) -> &Self {
+        self.route(path, Response::csv(rows))
+    }
+
+    /// Paths requested so far, relative to the base URL
+    pub fn requests(&self) -> Vec<String> {
+        self.requests
+            .lock()
+            .expect("not to be poisoned")
+            .iter()
+            .map(|path| path.trim_start_matches("/api").to_string())
+            .collect()
+    }
+
+    pub fn request_count(&self, path: &str) -> usize {
+        self.requests().iter().filter(|p| *p == path).count()
+    }
+
+    /// Largest number of held responses that were waiting at once
+    pub fn peak_held(&self) -> usize {
+        self.held.0.lock().expect("not to be poisoned").peak
+    }
+}
+
+/// Wait until `requests` held responses are waiting at once, or until they have been released
+fn hold(held: &(Mutex<Held>, Condvar), requests: usize) {
+    let (ref lock, ref condvar) = *held;
+    let mut state = lock.lock().expect("not to be poisoned");
+    state.waiting += 1;
+    state.peak = state.peak.max(state.waiting);
+    if state.waiting >= requests {
+        state.released = true;
+        condvar.notify_all();
+    }
+
+    let (mut state, _) = condvar
+        .wait_timeout_while(state, HOLD_TIMEOUT, |state| !state.released)
+        .expect("not to be poisoned");
+    state.waiting -= 1;
+}
+
+fn handle(
+    stream: TcpStream,
+    routes: &Mutex<HashMap<String, Vec<Response>>>,
+    requests: &Mutex<Vec<String>>,
+    held: &(Mutex<Held>, Condvar),
+) {
+    let mut reader = BufReader::new(stream.try_clone().expect("to clone stream"));
+    let mut request_line = String::new();
+    if reader.read_line(&mut request_line).is_err() {
+        return;
+    }
+
+    // Drain the headers; none of our requests have bodies
+    loop {
+        let mut line = String::new();
+        match reader.read_line(&mut line) {
+            Ok(0) | Err(_) => break,
+            Ok(_) if line == "\r\n" || line == "\n" => break,
+            Ok(_) => {}
+        }
+    }
+
+    let path = request_line
+        .split_whitespace()
+        .nth(1)
+        .unwrap_or("/")
+        .to_string();
+    requests
+        .lock()
+        .expect("not to be poisoned")
+        .push(path.clone());
+
+    let response = {
+        let mut routes = routes.lock().expect("not to be poisoned");
+        match routes.get_mut(&path) {
+            Some(ref mut queue) if queue.len() > 1 => queue.remove(0),
+            Some(ref queue) if !queue.is_empty() => queue[0].clone(),
+            _ => Response::status(404),
+        }
+    };
+    if let Some(requests) = response.held_until {
+        hold(held, requests);
+    }
+
+    let mut stream = stream;
+    let mut head = format!(
+        "HTTP/1.1 {} Mock\r\nContent-Length: {}\r\nConnection: close\r\n",
+        response.status,
+        response.body.len()
+    );
+    for (name, value) in &response.headers {
+        head.push_str(&format!("{}: {}\r\n", name, value));
+    }
+    head.push_str("\r\n");
+
+    let _ = stream.write_all(head.as_bytes());
+    let _ = stream.write_all(response.body.as_bytes());
+    let _ = stream.flush();
+}
+
+/// Encode JSON objects as CSV rows, with the keys of the first object as the header
+pub fn csv(rows: &[Value]) -> String {
+    fn field(value: &Value) -> String {
+        let value = match value {
+            Value::String(s) => s.clone(),
+            Value::Null => String::new(),
+            value => value.to_string(),
+        };
+        if value.contains(&[',', '"', '\n'][..]) {
+            format!("\"{}\"", value.replace('"', "\"\""))
+        } else {
+            value
+        }
+    }
+
+    let header: Vec<String> = match rows.first() {
+        Some(Value::Object(row)) => row.keys().cloned().collect(),
+        _ => return String::new(),
+    };
+    let mut body = header.join(",") + "\n";
+    for row in rows {
+        let fields: Vec<String> = header.iter().map(|key| field(&row[key])).collect();
+        body.push_str(&fields.join(","));
+        body.push('\n');
+    }
+    body
+}
+
+pub fn item(id: u64, name: &str) -> Value {
+    json!({
+        "data_id": id,
+        "name": name,
+        "rarity": 1,
+        "restriction_level": 0,
+        "img": format!("https://render.guildwars2.com/file/{}.png", id),
+        "type_id": 5,
+        "sub_type_id": 0,
+        "price_last_changed": TIMESTAMP,
+        "max_offer_unit_price": 100,
+        "min_sale_unit_price": 120,
+        "offer_availability": 1000,
+        "sale_availability": 2000,
+        "sale_price_change_last_hour": 0,
+        "offer_price_change_last_hour": 0
+    })
+}
+
+/// A single `item/{id}` response
+pub fn item_result(id: u64, name: &str) -> Value {
+    json!({ "result": item(id, name) })
+}
+
+/// A page of `items/...` or `item-search/...` results
+pub fn items_page(page: u64, last_page: u64, items: Vec<Value>) -> Value {
+    json!({
+        "count": items.len(),
+        "page": page,
+        "last_page": last_page,
+        "total": items.len() as u64 * last_page,
+        "results": items
+    })
+}
+
+pub fn listing(timestamp: &str, unit_price: u64) -> Value {
+    json!({
+        "listing_datetime": timestamp,
+        "unit_price": unit_price,
+        "quantity": 10,
+        "listings": 1
+    })
+}
+
+/// A page of `listings/{id}/{type}/...` results. Listings are sorted newest first.
+pub fn listings_page(listing_type: &str, page: u64, last_page: u64, listings: Vec<Value>) -> Value {
+    json!({
+        "sell-or-buy": listing_type,
+        "count": listings.len(),
+        "page": page,
+        "last_page": last_page,
+        "total": listings.len() as u64 * last_page,
+        "results": listings
+    })
+}
+
+/// A `types` response with a couple of types and sub-types
+pub fn types() -> Value {
+    json!({
+        "results": [
+            {
+                "id": 5,
+                "name": "Consumable",
+                "subtypes": []
+            },
+            {
+                "id": 18,
+                "name": "Weapon",
+                "subtypes": [
+                    { "id": 0, "name": "Sword" },
+                    { "id": 6, "name": "Greatsword" }
+                ]
+            }
+        ]
+    })
+}
+
+pub fn recipe(id: u64, name: &str) -> Value {
+    json!({
+        "data_id": id,
+        "name": name,
+        "result_count": 5,
+        "result_item_data_id": id + 1000,
+        "discipline_id": 1,
+        "result_item_max_offer_unit_price": 90,
+        "result_item_min_sale_unit_price": 110,
+        "crafting_cost": 80,
+        "rating": 25
+    })
+}
+
+/// A single `recipe/{id}` response, with ingredients
+pub fn recipe_result(id: u64, name: &str, ingredients: &[(u64, u64)]) -> Value {
+    let mut result = recipe(id, name);
+    result["ingredients"] = ingredients
+        .iter()
+        .map(|&(item_id, count)| json!({ "item_id": item_id, "count": count }))
+        .collect();
+    json!({ "result": result })
+}